@@ -0,0 +1,17 @@
+-- Postgres SQL
+
+-- ---------------------------
+-- Begin - ETH_CHECKPOINT -
+-- ---------------------------
+
+create table ETH_CHECKPOINT (
+    ID bigserial primary key,
+    VERSION int not null,
+    create_epoch_millis bigint not null,
+    update_epoch_millis bigint not null,
+    DATA JSONB
+);
+
+CREATE UNIQUE INDEX ETH_CHECKPOINT_INDEX_TOKEN_ADDRESS ON ETH_CHECKPOINT( (DATA->>'token_address') );
+
+-- End - ETH_CHECKPOINT -
@@ -0,0 +1,9 @@
+-- Postgres SQL
+
+-- ---------------------------
+-- Begin - ETH_EVENT -
+-- ---------------------------
+
+CREATE UNIQUE INDEX ETH_EVENT_UNIQUE_TRANSACTION_HASH_LOG_INDEX ON ETH_EVENT( (DATA->>'transaction_hash'), (DATA->>'log_index') );
+
+-- End - ETH_EVENT -
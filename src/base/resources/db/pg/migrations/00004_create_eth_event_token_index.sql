@@ -0,0 +1,9 @@
+-- Postgres SQL
+
+-- ---------------------------
+-- Begin - ETH_EVENT -
+-- ---------------------------
+
+CREATE INDEX ETH_EVENT_TOKEN_ADDRESS ON ETH_EVENT( (DATA->>'token_address') );
+
+-- End - ETH_EVENT -
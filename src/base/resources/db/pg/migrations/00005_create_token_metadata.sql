@@ -0,0 +1,17 @@
+-- Postgres SQL
+
+-- ---------------------------
+-- Begin - ETH_TOKEN_METADATA -
+-- ---------------------------
+
+create table ETH_TOKEN_METADATA (
+    ID bigserial primary key,
+    VERSION int not null,
+    create_epoch_millis bigint not null,
+    update_epoch_millis bigint not null,
+    DATA JSONB
+);
+
+CREATE UNIQUE INDEX ETH_TOKEN_METADATA_INDEX_TOKEN_ADDRESS ON ETH_TOKEN_METADATA( (DATA->>'token_address') );
+
+-- End - ETH_TOKEN_METADATA -
@@ -0,0 +1,17 @@
+-- Postgres SQL
+
+-- --------------------
+-- Begin - ETH_BALANCE -
+-- --------------------
+
+create table ETH_BALANCE (
+    ID bigserial primary key,
+    VERSION int not null,
+    create_epoch_millis bigint not null,
+    update_epoch_millis bigint not null,
+    DATA JSONB
+);
+
+CREATE UNIQUE INDEX ETH_BALANCE_INDEX_TOKEN_ADDRESS_ADDRESS ON ETH_BALANCE( (DATA->>'token_address'), (DATA->>'address') );
+
+-- End - ETH_BALANCE -
@@ -0,0 +1,17 @@
+-- Postgres SQL
+
+-- ----------------------
+-- Begin - ETH_ALLOWANCE -
+-- ----------------------
+
+create table ETH_ALLOWANCE (
+    ID bigserial primary key,
+    VERSION int not null,
+    create_epoch_millis bigint not null,
+    update_epoch_millis bigint not null,
+    DATA JSONB
+);
+
+CREATE UNIQUE INDEX ETH_ALLOWANCE_INDEX_TOKEN_ADDRESS_OWNER_SPENDER ON ETH_ALLOWANCE( (DATA->>'token_address'), (DATA->>'owner'), (DATA->>'spender') );
+
+-- End - ETH_ALLOWANCE -
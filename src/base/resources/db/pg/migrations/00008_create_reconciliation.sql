@@ -0,0 +1,17 @@
+-- Postgres SQL
+
+-- ---------------------------
+-- Begin - ETH_RECONCILIATION -
+-- ---------------------------
+
+create table ETH_RECONCILIATION (
+    ID bigserial primary key,
+    VERSION int not null,
+    create_epoch_millis bigint not null,
+    update_epoch_millis bigint not null,
+    DATA JSONB
+);
+
+CREATE INDEX ETH_RECONCILIATION_INDEX_TOKEN_ADDRESS ON ETH_RECONCILIATION( (DATA->>'token_address') );
+
+-- End - ETH_RECONCILIATION -
@@ -0,0 +1,16 @@
+-- Postgres SQL
+
+-- ---------------------------
+-- Begin - ETH_EVENT_LEGACY -
+-- ---------------------------
+
+-- The events persisted before the token address, block and transaction of each event were stored
+-- cannot be attributed to a token nor deduplicated. They are moved to an archive table,
+-- so that ETH_EVENT only contains rows with a complete provenance.
+create table if not exists ETH_EVENT_LEGACY (like ETH_EVENT including defaults);
+
+insert into ETH_EVENT_LEGACY select * from ETH_EVENT where DATA->>'transaction_hash' is null;
+
+delete from ETH_EVENT where DATA->>'transaction_hash' is null;
+
+-- End - ETH_EVENT_LEGACY -
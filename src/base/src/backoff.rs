@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+/// Exponential backoff policy with jitter for the retried operations, such as the reconnection attempts of
+/// a subscription or the persistence of a batch of events.
+///
+/// The delay starts from `initial_delay` and doubles after every failed attempt up to `max_delay`.
+/// A random fraction, up to `jitter`, of each delay is subtracted so that many tasks failing together
+/// do not retry at the same time.
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    max_attempts: Option<u32>,
+    attempts: u32,
+}
+
+impl Backoff {
+    /// Creates a new `Backoff`. If `max_attempts` is `None`, the attempts never stop.
+    pub fn new(initial_delay: Duration, max_delay: Duration, jitter: f64, max_attempts: Option<u32>) -> Self {
+        Self {
+            initial_delay,
+            max_delay: max_delay.max(initial_delay),
+            jitter: jitter.clamp(0.0, 1.0),
+            max_attempts,
+            attempts: 0,
+        }
+    }
+
+    /// Returns the delay to wait before the next attempt,
+    /// or `None` if the maximum number of attempts has been reached
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max_attempts| self.attempts >= max_attempts) {
+            return None;
+        }
+        let delay = self.initial_delay.saturating_mul(2u32.saturating_pow(self.attempts)).min(self.max_delay);
+        self.attempts += 1;
+        Some(delay.mul_f64(1.0 - self.jitter * rand::random::<f64>()))
+    }
+
+    /// Returns the number of attempts since the last reset
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Restarts from the initial delay after a successful attempt
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_double_the_delay_up_to_the_max() {
+        // Arrange
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(500), 0.0, None);
+
+        // Act
+        let delays: Vec<_> = (0..5).map(|_| backoff.next_delay().unwrap().as_millis()).collect();
+
+        // Assert
+        assert_eq!(vec![100, 200, 400, 500, 500], delays);
+    }
+
+    #[test]
+    fn should_stop_after_max_attempts_until_reset() {
+        // Arrange
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(500), 0.0, Some(2));
+
+        // Act & Assert
+        assert!(backoff.next_delay().is_some());
+        assert!(backoff.next_delay().is_some());
+        assert!(backoff.next_delay().is_none());
+
+        backoff.reset();
+        assert_eq!(Some(Duration::from_millis(100)), backoff.next_delay());
+    }
+
+    #[test]
+    fn should_apply_the_jitter() {
+        // Arrange
+        let mut backoff = Backoff::new(Duration::from_millis(1000), Duration::from_millis(1000), 0.5, None);
+
+        // Act & Assert
+        for _ in 0..100 {
+            let delay = backoff.next_delay().unwrap();
+            assert!(delay > Duration::from_millis(500) && delay <= Duration::from_millis(1000));
+        }
+    }
+}
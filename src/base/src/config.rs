@@ -1,6 +1,9 @@
+use alloy::primitives::Address;
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
 
+use crate::subscriber::model::{Finality, TokenStandard, Transport};
+
 /// Settings for the database connection
 #[derive(Debug, Deserialize)]
 pub struct Database {
@@ -18,13 +21,117 @@ pub struct Database {
     pub max_connections: u32,
 }
 
-/// Settings for the Ethereum node connection and the token address
+/// Settings for the Ethereum node connection and the token addresses
 /// for the subscription
 #[derive(Debug, Deserialize)]
 pub struct EthNode {
+    /// The time in seconds to wait for a new block before attempting a reconnection
     pub timeout_seconds: u64,
-    pub token_address: String,
     pub wss_url: String,
+    /// The transport used to receive the logs: ws or http
+    pub transport: Transport,
+    /// The HTTP URL of the Ethereum node, required by the http transport
+    pub http_url: Option<String>,
+    /// The URLs of the fallback endpoints of the node, in order of preference.
+    /// They must use the same transport of the primary endpoint.
+    #[serde(default)]
+    pub fallback_urls: Vec<String>,
+    /// The number of consecutive failures after which the active endpoint is replaced by the next one
+    pub max_endpoint_failures: u32,
+    /// The interval in seconds between two probes of the primary endpoint while a fallback one is active
+    pub failback_interval_seconds: u64,
+    /// The delay in milliseconds before the first reconnection attempt when no endpoint is reachable.
+    /// It doubles after each failed attempt.
+    pub reconnect_initial_delay_millis: u64,
+    /// The maximum delay in milliseconds between two reconnection attempts
+    pub reconnect_max_delay_millis: u64,
+    /// The fraction, between 0 and 1, of each reconnection delay that is randomized
+    pub reconnect_jitter: f64,
+    /// The optional maximum number of consecutive reconnection attempts. If not set, the attempts never stop.
+    pub reconnect_max_attempts: Option<u32>,
+    /// The interval in milliseconds between two polls of the node with the http transport
+    pub poll_interval_millis: u64,
+    /// The tokens to subscribe to
+    pub tokens: Vec<Token>,
+    /// The maximum number of blocks requested with a single `eth_getLogs` call during the backfill
+    pub backfill_chunk_size: u64,
+    /// The finality required before an event is forwarded: latest, safe or finalized
+    pub finality: Finality,
+    /// In `latest` mode, the number of blocks to be mined on top of the block of an event before it is forwarded
+    pub confirmations: u64,
+    /// The optional path of a JSONL file where every raw log received from the node is appended
+    pub record_path: Option<String>,
+    /// The optional path of a JSONL file of recorded logs to replay instead of connecting to the node
+    pub replay_path: Option<String>,
+    /// The speed of the replay relative to the recording, e.g. 10 replays the logs ten times faster.
+    /// If not set, the logs are replayed without delays.
+    pub replay_speed: Option<f64>,
+}
+
+/// Settings of a token contract to subscribe to
+#[derive(Debug, Clone, Deserialize)]
+pub struct Token {
+    /// The address of the token contract
+    pub address: Address,
+    /// An optional human readable name of the token, used in the logs
+    pub label: Option<String>,
+    /// The standard implemented by the token contract: erc20, weth, erc721 or erc1155. Defaults to erc20.
+    #[serde(default)]
+    pub standard: TokenStandard,
+    /// An optional path to a JSON ABI file of the contract.
+    /// The events declared in the ABI that are not part of the token standard are decoded at runtime.
+    pub abi_path: Option<String>,
+    /// The block from which the historical events are fetched before switching to the live subscription.
+    /// If not set, only the events emitted after the subscription are received.
+    pub start_block: Option<u64>,
+    /// The filters on the participants of the token events, applied by the node.
+    /// If not empty, only the events matching one of the filters are received.
+    #[serde(default)]
+    pub filters: Vec<EventFilter>,
+}
+
+/// A filter on the indexed participants of an event of the token standard
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventFilter {
+    /// The name of the event, e.g. `Transfer`
+    pub event: String,
+    /// If not empty, only the events sent from one of these addresses are received
+    #[serde(default)]
+    pub from: Vec<Address>,
+    /// If not empty, only the events sent to one of these addresses are received
+    #[serde(default)]
+    pub to: Vec<Address>,
+}
+
+/// Settings for the enrichment of the events with the details of their transaction
+#[derive(Debug, Deserialize)]
+pub struct Enrichment {
+    /// Whether the receipt of the transaction of each event is fetched and stored with the event
+    pub enabled: bool,
+    /// The maximum number of transactions whose details are cached
+    pub cache_size: usize,
+}
+
+/// Settings for the channels between the subscribers and the storage
+#[derive(Debug, Deserialize)]
+pub struct Pipeline {
+    /// The maximum number of messages waiting in a channel. When the channel is full, the sender waits
+    /// for the receiver to catch up.
+    pub channel_capacity: usize,
+}
+
+/// Settings for the periodic reconciliation of the derived balances with the token contracts
+#[derive(Debug, Deserialize)]
+pub struct Reconciliation {
+    /// Whether the reconciliation job is run
+    pub enabled: bool,
+    /// The interval in seconds between two reconciliations
+    pub interval_seconds: u64,
+    /// The number of top holders of each token whose balance is reconciled
+    pub sample_size: u32,
+    /// The addresses whose balance is always reconciled, in addition to the top holders
+    #[serde(default)]
+    pub addresses: Vec<Address>,
 }
 
 /// Settings for the local web server
@@ -46,6 +153,12 @@ pub struct Settings {
     pub database: Database,
     /// Ethereum node settings
     pub eth_node: EthNode,
+    /// Enrichment settings
+    pub enrichment: Enrichment,
+    /// Pipeline settings
+    pub pipeline: Pipeline,
+    /// Reconciliation settings
+    pub reconciliation: Reconciliation,
     /// Server settings
     pub server: Server,
 }
@@ -84,5 +197,6 @@ pub mod test {
 
         // Assert
         assert_eq!(5432, conf.database.port);
+        assert!(!conf.eth_node.tokens.is_empty());
     }
 }
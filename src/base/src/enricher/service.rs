@@ -0,0 +1,366 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use alloy::{primitives::B256, providers::Provider};
+use log::*;
+use tokio::{
+    sync::mpsc::{Receiver, Sender},
+    task::JoinHandle,
+    time::sleep,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    backoff::Backoff,
+    config::{Enrichment, EthNode},
+    endpoint::NodeConnection,
+    error::CoreError,
+    subscriber::model::{Event, SubscriberMessage, TransactionInfo},
+};
+
+/// Service for enriching the events with the details of the transaction that emitted them
+pub struct EnricherService {
+    connection: NodeConnection,
+    backoff: Backoff,
+    cache_size: usize,
+}
+
+impl EnricherService {
+    /// Creates a new instance of `EnricherService`.
+    /// The receipts are fetched from the HTTP endpoint of the node if configured, otherwise from the WebSocket one,
+    /// falling back to the fallback endpoints. The node is not contacted until the first event is received.
+    pub fn new(eth_node: &EthNode, enrichment: &Enrichment) -> Self {
+        info!("EnricherService - New instance created");
+        Self {
+            connection: NodeConnection::new(eth_node),
+            backoff: Backoff::new(
+                Duration::from_millis(eth_node.reconnect_initial_delay_millis),
+                Duration::from_millis(eth_node.reconnect_max_delay_millis),
+                eth_node.reconnect_jitter,
+                None,
+            ),
+            cache_size: enrichment.cache_size.max(1),
+        }
+    }
+
+    /// Subscribes to a receiver of subscriber messages and forwards them, in the same order, to the `sender`.
+    /// The function spawns a new tokio task and returns its join handle.
+    ///
+    /// The events are enriched with the details of their transaction, taken from its receipt which carries
+    /// the sender and the recipient of the transaction too. The details are cached by transaction hash; the cache
+    /// is cleared when a [`SubscriberMessage::ChainReorganized`] message is received, as the reorganized
+    /// transactions may be mined again with a different outcome.
+    /// The receipt is requested with an exponential backoff until the node returns it. Only the events of a block
+    /// that is no longer part of the chain are forwarded without enrichment, as they are removed by the chain
+    /// reorganization that follows them.
+    ///
+    /// The task terminates when all the senders of the `receiver` are dropped, dropping the `sender` in turn.
+    /// It also terminates if the `shutdown` token is cancelled while a receipt is being retried: the messages not
+    /// forwarded yet are dropped, and received again on restart as the checkpoint has not moved past them.
+    ///
+    /// # Errors
+    ///
+    /// The task returns an error if the `sender` channel is closed.
+    pub fn subscribe_to(
+        mut self,
+        mut receiver: Receiver<SubscriberMessage>,
+        sender: Sender<SubscriberMessage>,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<anyhow::Result<()>> {
+        info!("EnricherService - Subscribing to event stream");
+
+        let mut cache = TransactionCache::new(self.cache_size);
+
+        tokio::spawn(async move {
+            while let Some(mut message) = receiver.recv().await {
+                match &mut message {
+                    SubscriberMessage::Event(event) => {
+                        if let Some(transaction) = cache.get(&event.transaction_hash) {
+                            event.transaction = Some(transaction);
+                        } else {
+                            match self.fetch_transaction(event, &shutdown).await {
+                                Some(FetchedTransaction::Mined(transaction)) => {
+                                    cache.insert(event.transaction_hash, transaction.clone());
+                                    event.transaction = Some(transaction);
+                                }
+                                Some(FetchedTransaction::Reorganized) => warn!(
+                                    "EnricherService - Block {} was reorganized, the event of transaction {} is \
+                                     forwarded without enrichment",
+                                    event.block_number, event.transaction_hash
+                                ),
+                                None => {
+                                    info!("EnricherService - Stopped while fetching a transaction receipt");
+                                    return Ok(());
+                                }
+                            }
+                        }
+                    }
+                    SubscriberMessage::ChainReorganized { .. } => cache.clear(),
+                    SubscriberMessage::BlockProcessed { .. } => {}
+                }
+                sender.send(message).await?;
+            }
+            info!("EnricherService - Event stream closed");
+            Ok(())
+        })
+    }
+
+    /// Fetches the details of the transaction of an event, retrying with an exponential backoff until the receipt
+    /// is returned or the block of the event is found to be reorganized.
+    /// Returns `None` if the `shutdown` token is cancelled in the meantime.
+    async fn fetch_transaction(&mut self, event: &Event, shutdown: &CancellationToken) -> Option<FetchedTransaction> {
+        loop {
+            let provider = self.connection.provider(shutdown).await?;
+            match fetch_receipt(&provider, event).await {
+                Ok(Some(transaction)) => {
+                    self.connection.record_success();
+                    self.backoff.reset();
+                    return Some(transaction);
+                }
+                Ok(None) => warn!("EnricherService - Receipt of transaction {} not found", event.transaction_hash),
+                Err(err) => {
+                    self.connection.record_failure();
+                    warn!(
+                        "EnricherService - Failed to fetch the receipt of transaction {}: {err:?}",
+                        event.transaction_hash
+                    );
+                }
+            }
+
+            let delay = self.backoff.next_delay().unwrap_or_default();
+            debug!("EnricherService - Attempt {}. Retrying in {} ms", self.backoff.attempts(), delay.as_millis());
+            tokio::select! {
+                _ = sleep(delay) => {}
+                _ = shutdown.cancelled() => return None,
+            }
+        }
+    }
+}
+
+/// The outcome of the request of the receipt of the transaction of an event
+#[derive(Debug, PartialEq)]
+enum FetchedTransaction {
+    /// The details of the transaction, taken from its receipt
+    Mined(TransactionInfo),
+    /// The block of the event is no longer part of the chain
+    Reorganized,
+}
+
+/// Fetches the receipt of the transaction of an event.
+/// Returns `None` if the receipt is not available yet, e.g. because the node is behind the subscription.
+async fn fetch_receipt<P: Provider>(provider: &P, event: &Event) -> Result<Option<FetchedTransaction>, CoreError> {
+    if let Some(receipt) = provider.get_transaction_receipt(event.transaction_hash).await? {
+        return Ok(Some(FetchedTransaction::Mined(TransactionInfo {
+            from: receipt.from,
+            to: receipt.to,
+            gas_used: receipt.gas_used,
+            effective_gas_price: receipt.effective_gas_price,
+            status: receipt.status(),
+        })));
+    }
+
+    // A missing receipt is retried, unless the block of the event has been replaced
+    match provider.get_block_by_number(event.block_number.into()).await? {
+        Some(block) if block.header.hash != event.block_hash => Ok(Some(FetchedTransaction::Reorganized)),
+        _ => Ok(None),
+    }
+}
+
+/// A bounded cache of the transaction details by hash. When full, the oldest entry is evicted.
+struct TransactionCache {
+    capacity: usize,
+    entries: HashMap<B256, TransactionInfo>,
+    order: VecDeque<B256>,
+}
+
+impl TransactionCache {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::new(), order: VecDeque::new() }
+    }
+
+    fn get(&self, transaction_hash: &B256) -> Option<TransactionInfo> {
+        self.entries.get(transaction_hash).cloned()
+    }
+
+    fn insert(&mut self, transaction_hash: B256, transaction: TransactionInfo) {
+        if self.entries.insert(transaction_hash, transaction).is_some() {
+            return;
+        }
+        self.order.push_back(transaction_hash);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use alloy::{
+        primitives::{Address, Bloom},
+        providers::ProviderBuilder,
+        rpc::types::{Block, Header},
+        transports::mock::Asserter,
+    };
+    use serde_json::json;
+
+    use super::*;
+    use crate::subscriber::model::EventType;
+
+    fn random_event() -> Event {
+        Event {
+            token_address: Address::random(),
+            block_number: 100,
+            block_hash: B256::random(),
+            block_timestamp: 1_700_000_000,
+            transaction_hash: B256::random(),
+            transaction_index: 0,
+            log_index: 0,
+            event_type: EventType::Deposit { to: Address::random(), value: Default::default() },
+            transaction: None,
+        }
+    }
+
+    fn block_with_hash(hash: B256) -> Block {
+        Block { header: Header { hash, ..Default::default() }, ..Default::default() }
+    }
+
+    fn random_transaction() -> TransactionInfo {
+        TransactionInfo {
+            from: Address::random(),
+            to: Some(Address::random()),
+            gas_used: 21_000,
+            effective_gas_price: 1_000_000_000,
+            status: true,
+        }
+    }
+
+    #[test]
+    fn should_evict_the_oldest_transactions() {
+        // Arrange
+        let mut cache = TransactionCache::new(2);
+        let hashes = [B256::random(), B256::random(), B256::random()];
+        let transaction = random_transaction();
+
+        // Act
+        for hash in hashes {
+            cache.insert(hash, transaction.clone());
+        }
+
+        // Assert
+        assert!(cache.get(&hashes[0]).is_none());
+        assert_eq!(Some(transaction.clone()), cache.get(&hashes[1]));
+        assert_eq!(Some(transaction), cache.get(&hashes[2]));
+    }
+
+    #[test]
+    fn should_clear_the_cache() {
+        // Arrange
+        let mut cache = TransactionCache::new(2);
+        let hash = B256::random();
+        cache.insert(hash, random_transaction());
+
+        // Act
+        cache.clear();
+
+        // Assert
+        assert!(cache.get(&hash).is_none());
+        assert!(cache.order.is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_take_the_transaction_from_the_receipt() {
+        // Arrange
+        let event = random_event();
+        let (from, to) = (Address::random(), Address::random());
+        let asserter = Asserter::new();
+        asserter.push_success(&json!({
+            "type": "0x2",
+            "status": "0x1",
+            "cumulativeGasUsed": "0x5208",
+            "logs": [],
+            "logsBloom": Bloom::default(),
+            "transactionHash": event.transaction_hash,
+            "transactionIndex": "0x0",
+            "blockHash": event.block_hash,
+            "blockNumber": "0x64",
+            "gasUsed": "0x5208",
+            "effectiveGasPrice": "0x3b9aca00",
+            "from": from,
+            "to": to,
+            "contractAddress": null,
+        }));
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter);
+
+        // Act
+        let transaction = fetch_receipt(&provider, &event).await.unwrap();
+
+        // Assert
+        assert_eq!(
+            Some(FetchedTransaction::Mined(TransactionInfo {
+                from,
+                to: Some(to),
+                gas_used: 21_000,
+                effective_gas_price: 1_000_000_000,
+                status: true,
+            })),
+            transaction
+        );
+    }
+
+    #[tokio::test]
+    async fn should_retry_a_missing_receipt_of_a_canonical_block() {
+        // Arrange
+        let event = random_event();
+        let asserter = Asserter::new();
+        asserter.push_success(&None::<()>);
+        asserter.push_success(&block_with_hash(event.block_hash));
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter);
+
+        // Act
+        let transaction = fetch_receipt(&provider, &event).await.unwrap();
+
+        // Assert
+        assert_eq!(None, transaction);
+    }
+
+    #[tokio::test]
+    async fn should_not_retry_a_missing_receipt_of_a_reorganized_block() {
+        // Arrange
+        let event = random_event();
+        let asserter = Asserter::new();
+        asserter.push_success(&None::<()>);
+        asserter.push_success(&block_with_hash(B256::random()));
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter);
+
+        // Act
+        let transaction = fetch_receipt(&provider, &event).await.unwrap();
+
+        // Assert
+        assert_eq!(Some(FetchedTransaction::Reorganized), transaction);
+    }
+
+    #[tokio::test]
+    async fn should_fail_the_receipt_when_the_node_is_unreachable() {
+        // Arrange
+        let event = random_event();
+        let asserter = Asserter::new();
+        asserter.push_failure_msg("connection refused");
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter);
+
+        // Act
+        let result = fetch_receipt(&provider, &event).await;
+
+        // Assert
+        assert!(matches!(result, Err(CoreError::NodeError(_))));
+    }
+}
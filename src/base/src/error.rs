@@ -1,6 +1,8 @@
+use alloy::transports::TransportError;
 use c3p0::C3p0Error;
 use sqlx::migrate::MigrateError;
 use thiserror::Error;
+use tokio::sync::mpsc::error::SendError;
 
 /// Common error type
 #[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -10,6 +12,18 @@ pub enum CoreError {
 
     #[error("Database Migration error: {0}")]
     DatabaseMigrationError(String),
+
+    #[error("Ethereum node error: {0}")]
+    NodeError(String),
+
+    #[error("Log decoding error: {0}")]
+    DecodeError(String),
+
+    #[error("The message channel is closed")]
+    ChannelClosed,
+
+    #[error("Balance overflow: {0}")]
+    BalanceOverflow(String),
 }
 
 impl From<C3p0Error> for CoreError {
@@ -18,8 +32,26 @@ impl From<C3p0Error> for CoreError {
     }
 }
 
+impl From<sqlx::Error> for CoreError {
+    fn from(err: sqlx::Error) -> Self {
+        CoreError::DatabaseError(format!("{:?}", err))
+    }
+}
+
 impl From<MigrateError> for CoreError {
     fn from(err: MigrateError) -> Self {
         CoreError::DatabaseMigrationError(format!("{:?}", err))
     }
 }
+
+impl From<TransportError> for CoreError {
+    fn from(err: TransportError) -> Self {
+        CoreError::NodeError(format!("{:?}", err))
+    }
+}
+
+impl<T> From<SendError<T>> for CoreError {
+    fn from(_: SendError<T>) -> Self {
+        CoreError::ChannelClosed
+    }
+}
@@ -1,4 +1,8 @@
+pub mod backoff;
 pub mod config;
+pub mod endpoint;
+pub mod enricher;
 pub mod error;
+pub mod reconciler;
 pub mod storage;
 pub mod subscriber;
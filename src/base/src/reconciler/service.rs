@@ -0,0 +1,231 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    eips::BlockId,
+    primitives::{Address, U256},
+    providers::DynProvider,
+};
+use log::*;
+use tokio::{
+    task::JoinHandle,
+    time::{Instant, interval_at},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{EthNode, Reconciliation, Token},
+    endpoint::NodeConnection,
+    storage::{
+        model::{DiscrepancyData, LedgerSnapshot, ReconciliationData},
+        service::StorageService,
+    },
+    subscriber::{decoder::IERC20, model::TokenStandard},
+};
+
+/// Service for reconciling the balances derived from the stored events with the ones returned by the token contracts
+pub struct ReconcilerService {
+    connection: NodeConnection,
+    tokens: Vec<Token>,
+    interval: Duration,
+    sample_size: u32,
+    addresses: Vec<Address>,
+    storage: Arc<StorageService>,
+}
+
+impl ReconcilerService {
+    /// Creates a new instance of `ReconcilerService`.
+    /// Only the erc20 and weth tokens are reconciled, as the balances are derived from their fungible transfers.
+    /// The tokens with event filters are skipped too, as their stored events are a subset of the emitted ones.
+    /// The contracts are queried through the HTTP endpoint of the node if configured, otherwise the WebSocket one,
+    /// falling back to the fallback endpoints. The node is not contacted until the first reconciliation.
+    pub fn new(eth_node: &EthNode, reconciliation: &Reconciliation, storage: Arc<StorageService>) -> Self {
+        info!("ReconcilerService - New instance created");
+        Self {
+            connection: NodeConnection::new(eth_node),
+            tokens: eth_node
+                .tokens
+                .iter()
+                .filter(|token| {
+                    matches!(token.standard, TokenStandard::Erc20 | TokenStandard::Weth) && token.filters.is_empty()
+                })
+                .cloned()
+                .collect(),
+            interval: Duration::from_secs(reconciliation.interval_seconds.max(1)),
+            sample_size: reconciliation.sample_size,
+            addresses: reconciliation.addresses.clone(),
+            storage,
+        }
+    }
+
+    /// Starts the periodic reconciliation of the tokens.
+    /// The function spawns a new tokio task and returns its join handle.
+    ///
+    /// At each interval, for each token with a checkpoint, the balances of the configured addresses and of the
+    /// top holders, and the total supply, derived from the events persisted up to the checkpoint are compared
+    /// with the `balanceOf` and `totalSupply` of the token contract at the block of the checkpoint.
+    /// The outcome, with the discrepancies found, is persisted. A token that cannot be reconciled, e.g. because
+    /// the node does not serve the historical state, is skipped until the next interval. If no endpoint of the
+    /// node can be reached, the connection is retried with an exponential backoff.
+    ///
+    /// The task terminates when the `shutdown` token is cancelled.
+    pub fn start(mut self, shutdown: CancellationToken) -> JoinHandle<()> {
+        info!("ReconcilerService - Starting the reconciliation every {:?}", self.interval);
+
+        tokio::spawn(async move {
+            let mut interval = interval_at(Instant::now() + self.interval, self.interval);
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+
+                let Some(provider) = self.connection.provider(&shutdown).await else {
+                    break;
+                };
+
+                for token in &self.tokens {
+                    let name = token.label.clone().unwrap_or_else(|| token.address.to_string());
+                    match self.reconcile(&provider, token.address).await {
+                        Ok(Some(reconciliation)) => {
+                            self.connection.record_success();
+                            if reconciliation.discrepancies.is_empty() {
+                                info!(
+                                    "The balances of token {name} are consistent at block {}",
+                                    reconciliation.block_number
+                                );
+                            } else {
+                                warn!(
+                                    "Found {} discrepancies in the balances of token {name} at block {}",
+                                    reconciliation.discrepancies.len(),
+                                    reconciliation.block_number
+                                );
+                            }
+                            if let Err(err) = self.storage.save_reconciliation(reconciliation).await {
+                                error!("Failed to save the reconciliation of token {name}: {err:?}");
+                            }
+                        }
+                        Ok(None) => debug!("Token {name} has no checkpoint yet, the reconciliation is skipped"),
+                        Err(err) => {
+                            warn!("Failed to reconcile the balances of token {name}: {err:?}");
+                            if err.is::<alloy::contract::Error>() {
+                                self.connection.record_failure();
+                            }
+                        }
+                    }
+                }
+            }
+            info!("ReconcilerService - Reconciliation stopped");
+        })
+    }
+
+    /// Reconciles the balances of a token at its checkpoint, if any
+    async fn reconcile(
+        &self,
+        provider: &DynProvider,
+        token_address: Address,
+    ) -> anyhow::Result<Option<ReconciliationData>> {
+        let Some(snapshot) =
+            self.storage.fetch_ledger_snapshot(&token_address, &self.addresses, self.sample_size).await?
+        else {
+            return Ok(None);
+        };
+
+        // Query the block by hash, so that the call fails if the checkpoint was reorganized in the meantime
+        let block = BlockId::hash(snapshot.block_hash);
+        let contract = IERC20::new(token_address, provider);
+        let mut balances = Vec::with_capacity(snapshot.balances.len());
+        for (address, _) in &snapshot.balances {
+            balances.push(contract.balanceOf(*address).block(block).call().await?);
+        }
+        let total_supply = contract.totalSupply().block(block).call().await?;
+
+        Ok(Some(ReconciliationData {
+            token_address,
+            block_number: snapshot.block_number,
+            block_hash: snapshot.block_hash,
+            reconciled_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()),
+            checked_addresses: snapshot.balances.len() as u32,
+            discrepancies: find_discrepancies(&snapshot, &balances, total_supply),
+        }))
+    }
+}
+
+/// Compares the derived balances of a snapshot with the on-chain ones, given in the same order,
+/// and returns the ones that differ
+fn find_discrepancies(snapshot: &LedgerSnapshot, balances: &[U256], total_supply: U256) -> Vec<DiscrepancyData> {
+    snapshot
+        .balances
+        .iter()
+        .zip(balances)
+        .map(|((address, derived), on_chain)| (Some(*address), *derived, *on_chain))
+        .chain([(None, snapshot.total_supply, total_supply)])
+        .filter(|(_, derived, on_chain)| derived.is_negative() || derived.into_raw() != *on_chain)
+        .map(|(address, derived, on_chain)| DiscrepancyData { address, derived, on_chain })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use alloy::primitives::{B256, I256};
+
+    use super::*;
+
+    /// Tests that only the balances that differ from the on-chain ones are reported
+    #[test]
+    fn should_find_the_discrepancies() {
+        // Arrange
+        let alice = Address::random();
+        let bob = Address::random();
+        let carol = Address::random();
+        let snapshot = LedgerSnapshot {
+            token_address: Address::random(),
+            block_number: 100,
+            block_hash: B256::random(),
+            balances: vec![
+                (alice, I256::try_from(10).unwrap()),
+                (bob, I256::try_from(-5).unwrap()),
+                (carol, I256::ZERO),
+            ],
+            total_supply: I256::try_from(20).unwrap(),
+        };
+
+        // Act
+        let discrepancies =
+            find_discrepancies(&snapshot, &[U256::from(10), U256::from(5), U256::from(1)], U256::from(20));
+
+        // Assert
+        assert_eq!(
+            vec![
+                DiscrepancyData { address: Some(bob), derived: I256::try_from(-5).unwrap(), on_chain: U256::from(5) },
+                DiscrepancyData { address: Some(carol), derived: I256::ZERO, on_chain: U256::from(1) },
+            ],
+            discrepancies
+        );
+    }
+
+    /// Tests that a different total supply is reported without an address
+    #[test]
+    fn should_find_the_total_supply_discrepancy() {
+        // Arrange
+        let snapshot = LedgerSnapshot {
+            token_address: Address::random(),
+            block_number: 100,
+            block_hash: B256::random(),
+            balances: vec![],
+            total_supply: I256::try_from(20).unwrap(),
+        };
+
+        // Act
+        let discrepancies = find_discrepancies(&snapshot, &[], U256::from(21));
+
+        // Assert
+        assert_eq!(
+            vec![DiscrepancyData { address: None, derived: I256::try_from(20).unwrap(), on_chain: U256::from(21) }],
+            discrepancies
+        );
+    }
+}
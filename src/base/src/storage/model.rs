@@ -1,24 +1,292 @@
-use alloy::primitives::{Address, U256};
+use alloy::primitives::{Address, B256, I256, U256};
 use c3p0::Model;
 use serde::{Deserialize, Serialize};
 use strum::{AsRefStr, Display, EnumDiscriminants};
 
+use crate::error::CoreError;
+
 pub type EthEventModel = Model<u64, EthEventData>;
+pub type CheckpointModel = Model<u64, CheckpointData>;
+pub type TokenMetadataModel = Model<u64, TokenMetadataData>;
+pub type BalanceModel = Model<u64, BalanceData>;
+pub type AllowanceModel = Model<u64, AllowanceData>;
+pub type ReconciliationModel = Model<u64, ReconciliationData>;
 
 /// Ethereum event data for a ERC20 contract subscription
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct EthEventData {
+    /// The address of the contract that emitted the event
+    pub token_address: Address,
+    /// The amount transferred or approved. It is zero for the events that do not carry a single amount.
     pub value: U256,
     pub event_type: EthEventType,
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// The block timestamp in seconds since the Unix epoch
+    pub block_timestamp: u64,
+    pub transaction_hash: B256,
+    pub transaction_index: u64,
+    pub log_index: u64,
+    /// The details of the transaction that emitted the event, if the enrichment is enabled
+    #[serde(default)]
+    pub transaction: Option<EthTransactionData>,
+}
+
+/// The details of the transaction that emitted an Ethereum event
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EthTransactionData {
+    /// The account that initiated the transaction
+    pub from: Address,
+    /// The recipient of the transaction, `None` for a contract creation
+    pub to: Option<Address>,
+    pub gas_used: u64,
+    /// The price per unit of gas paid by the transaction, in wei
+    pub effective_gas_price: u128,
+    /// True if the transaction succeeded
+    pub status: bool,
+}
+
+/// Outcome of the persistence of an Ethereum event.
+/// Events are identified by the hash of their transaction and their log index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PersistedEvent {
+    /// The event was not stored yet and it has been persisted
+    New(EthEventModel),
+    /// The event was already stored; the previously persisted model is returned
+    Duplicate(EthEventModel),
 }
 
-/// Ethereum event type for a ERC20 contract subscription
+impl EthEventData {
+    /// Returns the balance changes caused by the event, one for each affected address.
+    /// Only Deposit, Withdrawal and Transfer events move the balances; the zero address of mints and burns
+    /// is not tracked.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the value of the event does not fit in a signed balance, as only a hostile contract
+    /// can emit it.
+    pub fn balance_changes(&self) -> Result<Vec<(Address, I256)>, CoreError> {
+        let value = I256::try_from(self.value)
+            .map_err(|_| CoreError::BalanceOverflow(format!("The value {} does not fit in a balance", self.value)))?;
+        // The negation of a non negative value never overflows
+        let changes = match &self.event_type {
+            EthEventType::Transfer { from, to } => vec![(*from, -value), (*to, value)],
+            EthEventType::Deposit { to } => vec![(*to, value)],
+            EthEventType::Withdrawal { from } => vec![(*from, -value)],
+            _ => vec![],
+        };
+        Ok(changes.into_iter().filter(|(address, _)| !address.is_zero()).collect())
+    }
+
+    /// Returns the allowance change caused by the event, if any.
+    /// An Approval sets the allowance of the spender, while a Transfer spends it when the sender of the transaction
+    /// is not the owner of the tokens. Spending requires the transaction details of the enrichment.
+    pub fn allowance_change(&self) -> Option<AllowanceChange> {
+        match &self.event_type {
+            EthEventType::Approve { from, to } => {
+                Some(AllowanceChange::Set { owner: *from, spender: *to, allowance: self.value })
+            }
+            EthEventType::Transfer { from, .. } => match &self.transaction {
+                Some(transaction) if transaction.from != *from => {
+                    Some(AllowanceChange::Spend { owner: *from, spender: transaction.from, amount: self.value })
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl PersistedEvent {
+    /// Returns the persisted event model
+    pub fn model(&self) -> &EthEventModel {
+        match self {
+            PersistedEvent::New(model) | PersistedEvent::Duplicate(model) => model,
+        }
+    }
+
+    /// Returns true if the event was not stored before
+    pub fn is_new(&self) -> bool {
+        matches!(self, PersistedEvent::New(_))
+    }
+}
+
+/// Notification sent by the `StorageService` when a message received from the subscriber is processed
+#[derive(Clone, Debug, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum StorageEvent {
+    /// An Ethereum event was persisted
+    Persisted(PersistedEvent),
+    /// A chain reorganization was processed: the events from `block_number` onward were deleted
+    ChainReorganized { token_address: Address, block_number: u64, removed_events: u64 },
+}
+
+/// Ethereum event type for a token contract subscription
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, AsRefStr, Display, EnumDiscriminants)]
 #[strum_discriminants(derive(Serialize, Deserialize, AsRefStr, Display))]
 #[serde(tag = "type")]
 pub enum EthEventType {
-    Approve { from: Address, to: Address },
-    Transfer { from: Address, to: Address },
-    Deposit { to: Address },
-    Withdrawal { from: Address },
+    Approve {
+        from: Address,
+        to: Address,
+    },
+    Transfer {
+        from: Address,
+        to: Address,
+    },
+    Deposit {
+        to: Address,
+    },
+    Withdrawal {
+        from: Address,
+    },
+    /// ERC721 approval of a single token
+    NftApproval {
+        owner: Address,
+        approved: Address,
+        token_id: U256,
+    },
+    /// ERC721 transfer of a single token
+    NftTransfer {
+        from: Address,
+        to: Address,
+        token_id: U256,
+    },
+    /// ERC721 and ERC1155 approval of an operator for all the tokens of the owner
+    ApprovalForAll {
+        owner: Address,
+        operator: Address,
+        approved: bool,
+    },
+    /// ERC1155 transfer of the `value` amount of a single token
+    TransferSingle {
+        operator: Address,
+        from: Address,
+        to: Address,
+        id: U256,
+    },
+    /// ERC1155 transfer of multiple tokens, `values[i]` is the amount of `ids[i]`
+    TransferBatch {
+        operator: Address,
+        from: Address,
+        to: Address,
+        ids: Vec<U256>,
+        values: Vec<U256>,
+    },
+    /// ERC1155 update of the URI of a token
+    Uri {
+        id: U256,
+        value: String,
+    },
+    /// An event decoded with a runtime-loaded ABI
+    Generic {
+        name: String,
+        params: Vec<EthEventParam>,
+    },
+}
+
+/// A parameter of an event decoded with a runtime-loaded ABI
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EthEventParam {
+    pub name: String,
+    /// The canonical Solidity type of the parameter
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub value: serde_json::Value,
+}
+
+/// Ingestion checkpoint of a token subscription.
+/// It points to the last block whose events were all persisted.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CheckpointData {
+    pub token_address: Address,
+    pub block_number: u64,
+    pub block_hash: B256,
+}
+
+/// Metadata of a token contract, read from the contract itself.
+/// Each field is `None` if the contract does not implement the corresponding optional method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TokenMetadataData {
+    pub token_address: Address,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    /// The number of decimals used to represent the token amounts
+    pub decimals: Option<u8>,
+}
+
+/// Balance of an address, derived from the Deposit, Withdrawal and Transfer events of a token.
+/// The balance can be negative if the events were not ingested from the deployment of the contract.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BalanceData {
+    pub token_address: Address,
+    pub address: Address,
+    pub balance: I256,
+}
+
+/// Allowance granted by an owner to a spender, derived from the Approval and Transfer events of a token
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AllowanceData {
+    pub token_address: Address,
+    pub owner: Address,
+    pub spender: Address,
+    pub allowance: U256,
+}
+
+/// A change of the allowance granted by an owner to a spender
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AllowanceChange {
+    /// The allowance is set by an Approval event
+    Set { owner: Address, spender: Address, allowance: U256 },
+    /// The allowance is spent by a Transfer event initiated by the spender.
+    /// Following the WETH contract, an allowance of `U256::MAX` is never decremented.
+    Spend { owner: Address, spender: Address, amount: U256 },
+}
+
+impl AllowanceChange {
+    /// Returns the owner and the spender of the allowance
+    pub fn parties(&self) -> (Address, Address) {
+        match self {
+            AllowanceChange::Set { owner, spender, .. } | AllowanceChange::Spend { owner, spender, .. } => {
+                (*owner, *spender)
+            }
+        }
+    }
+}
+
+/// The balances of a token derived from the events persisted up to its checkpoint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LedgerSnapshot {
+    pub token_address: Address,
+    /// The block of the checkpoint
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// The balances of the requested addresses
+    pub balances: Vec<(Address, I256)>,
+    /// The sum of all the balances, which is the supply minted by Deposit and Transfer events from the zero address
+    pub total_supply: I256,
+}
+
+/// Outcome of the reconciliation of the derived balances of a token with the token contract
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReconciliationData {
+    pub token_address: Address,
+    /// The block at which the balances were compared
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// The time of the reconciliation in seconds since the Unix epoch
+    pub reconciled_at: u64,
+    /// The number of addresses whose balance was compared
+    pub checked_addresses: u32,
+    /// The balances that differ from the ones returned by the token contract
+    pub discrepancies: Vec<DiscrepancyData>,
+}
+
+/// A balance derived from the stored events that differs from the one returned by the token contract
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DiscrepancyData {
+    /// The address of the holder, `None` for the total supply
+    pub address: Option<Address>,
+    pub derived: I256,
+    pub on_chain: U256,
 }
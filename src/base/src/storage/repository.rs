@@ -1,11 +1,17 @@
-use ::sqlx::PgConnection;
+use ::sqlx::{PgConnection, Row};
+use alloy::primitives::{Address, B256, I256, U256};
 use c3p0::sqlx::*;
+use c3p0::time::utils::get_current_epoch_millis;
 use c3p0::*;
 use log::trace;
 
 use crate::error::CoreError;
 
-use super::model::{EthEventData, EthEventModel, EthEventTypeDiscriminants};
+use super::model::{
+    AllowanceChange, AllowanceData, AllowanceModel, BalanceData, BalanceModel, CheckpointData, CheckpointModel,
+    EthEventData, EthEventModel, EthEventTypeDiscriminants, PersistedEvent, ReconciliationData, ReconciliationModel,
+    TokenMetadataData, TokenMetadataModel,
+};
 
 /// An Ethereum event repository that persists events in the ETH_EVENT table of a Postgres database
 #[derive(Clone)]
@@ -26,6 +32,7 @@ impl EthEventRepository {
     }
 
     /// Fetches all Ethereum events from the database starting from the given `from_id` up to `limit` events.
+    /// If a `token_address` is provided, only the events emitted by that contract are returned.
     ///
     /// The events are sorted in ascending order by `id`.
     ///
@@ -35,24 +42,38 @@ impl EthEventRepository {
     pub async fn fetch_all(
         &self,
         tx: &mut PgConnection,
+        token_address: Option<&Address>,
         from_id: &u64,
         limit: u32,
     ) -> Result<Vec<EthEventModel>, CoreError> {
-        trace!("Fetching all events from the database, from id: {}, limit: {}", from_id, limit);
+        trace!(
+            "Fetching all events from the database, token: {:?}, from id: {}, limit: {}",
+            token_address, from_id, limit
+        );
         let sql = format!(
             r#"
             {}
-            where id >= $1
+            where id >= $1 and ($2::text is null or DATA ->> 'token_address' = $2)
             order by id asc
-            limit $2
+            limit $3
         "#,
             self.repo.queries().find_base_sql_query
         );
 
-        Ok(self.repo.fetch_all_with_sql(tx, self.repo.query_with_id(&sql, from_id).bind(limit as i64)).await?)
+        Ok(self
+            .repo
+            .fetch_all_with_sql(
+                tx,
+                self.repo
+                    .query_with_id(&sql, from_id)
+                    .bind(token_address.map(|address| format!("{address:#x}")))
+                    .bind(limit as i64),
+            )
+            .await?)
     }
 
     /// Fetches all Ethereum events of a given type from the database starting from the given `from_id` up to `limit` events.
+    /// If a `token_address` is provided, only the events emitted by that contract are returned.
     ///
     /// The events are sorted in ascending order by `id`.
     ///
@@ -63,35 +84,709 @@ impl EthEventRepository {
         &self,
         tx: &mut PgConnection,
         event_type: EthEventTypeDiscriminants,
+        token_address: Option<&Address>,
         from_id: &u64,
         limit: u32,
     ) -> Result<Vec<EthEventModel>, CoreError> {
-        trace!("Fetching all events of type {} from the database, from id: {}, limit: {}", event_type, from_id, limit);
+        trace!(
+            "Fetching all events of type {} from the database, token: {:?}, from id: {}, limit: {}",
+            event_type, token_address, from_id, limit
+        );
 
         let sql = format!(
             r#"
             {}
             where id >= $1 and DATA -> 'event_type' ->> 'type' = $2
+            and ($3::text is null or DATA ->> 'token_address' = $3)
             order by id asc
-            limit $3
+            limit $4
+        "#,
+            self.repo.queries().find_base_sql_query
+        );
+
+        Ok(self
+            .repo
+            .fetch_all_with_sql(
+                tx,
+                self.repo
+                    .query_with_id(&sql, from_id)
+                    .bind(event_type.as_ref())
+                    .bind(token_address.map(|address| format!("{address:#x}")))
+                    .bind(limit as i64),
+            )
+            .await?)
+    }
+
+    /// Fetches the Ethereum event emitted by the given transaction with the given log index, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_by_chain_position(
+        &self,
+        tx: &mut PgConnection,
+        transaction_hash: &B256,
+        log_index: u64,
+    ) -> Result<Option<EthEventModel>, CoreError> {
+        trace!(
+            "Fetching event with transaction hash {} and log index {} from the database",
+            transaction_hash, log_index
+        );
+        let sql = format!(
+            r#"
+            {}
+            where DATA ->> 'transaction_hash' = $1 and DATA ->> 'log_index' = $2
         "#,
             self.repo.queries().find_base_sql_query
         );
 
         Ok(self
             .repo
-            .fetch_all_with_sql(tx, self.repo.query_with_id(&sql, from_id).bind(event_type.as_ref()).bind(limit as i64))
+            .fetch_one_optional_with_sql(
+                tx,
+                ::sqlx::query(&sql).bind(format!("{transaction_hash:#x}")).bind(log_index.to_string()),
+            )
             .await?)
     }
 
-    /// Saves an Ethereum event to the database.
-    /// If successful, it returns the saved event model populated with the generated id.
+    /// Fetches all the Ethereum events of the given token emitted from the given block onward.
     ///
     /// # Errors
     ///
     /// Returns `Err` if there is an error interacting with the database.
-    pub async fn save(&self, tx: &mut PgConnection, model: NewModel<EthEventData>) -> Result<EthEventModel, CoreError> {
-        trace!("Saving event to the database: {:?}", model);
-        Ok(self.repo.save(tx, model).await?)
+    pub async fn fetch_from_block(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+        block_number: u64,
+    ) -> Result<Vec<EthEventModel>, CoreError> {
+        trace!("Fetching all events of token {} from block {} from the database", token_address, block_number);
+        let sql = format!(
+            r#"
+            {}
+            where DATA ->> 'token_address' = $1 and (DATA ->> 'block_number')::bigint >= $2
+            order by id asc
+        "#,
+            self.repo.queries().find_base_sql_query
+        );
+
+        Ok(self
+            .repo
+            .fetch_all_with_sql(tx, ::sqlx::query(&sql).bind(format!("{token_address:#x}")).bind(block_number as i64))
+            .await?)
+    }
+
+    /// Fetches all the Ethereum events of the given token that changed the allowance granted by `owner`
+    /// to `spender`: the Approval events between them and the Transfer events of the tokens of `owner`
+    /// in transactions sent by `spender`.
+    ///
+    /// The events are sorted in ascending order by `id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_allowance_events(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+        owner: &Address,
+        spender: &Address,
+    ) -> Result<Vec<EthEventModel>, CoreError> {
+        trace!(
+            "Fetching the allowance events of token {} from owner {} to spender {} from the database",
+            token_address, owner, spender
+        );
+        let sql = format!(
+            r#"
+            {}
+            where DATA ->> 'token_address' = $1 and DATA -> 'event_type' ->> 'from' = $2
+            and (
+                (DATA -> 'event_type' ->> 'type' = 'Approve' and DATA -> 'event_type' ->> 'to' = $3)
+                or (DATA -> 'event_type' ->> 'type' = 'Transfer' and DATA -> 'transaction' ->> 'from' = $3)
+            )
+            order by id asc
+        "#,
+            self.repo.queries().find_base_sql_query
+        );
+
+        Ok(self
+            .repo
+            .fetch_all_with_sql(
+                tx,
+                ::sqlx::query(&sql)
+                    .bind(format!("{token_address:#x}"))
+                    .bind(format!("{owner:#x}"))
+                    .bind(format!("{spender:#x}")),
+            )
+            .await?)
+    }
+
+    /// Deletes all the Ethereum events of the given token emitted from the given block onward.
+    /// It returns the number of deleted events.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn delete_from_block(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+        block_number: u64,
+    ) -> Result<u64, CoreError> {
+        trace!("Deleting all events of token {} from block {} from the database", token_address, block_number);
+        let sql = format!(
+            r#"
+            DELETE FROM {}
+            where DATA ->> 'token_address' = $1 and (DATA ->> 'block_number')::bigint >= $2
+        "#,
+            self.repo.queries().qualified_table_name
+        );
+
+        Ok(::sqlx::query(&sql)
+            .bind(format!("{token_address:#x}"))
+            .bind(block_number as i64)
+            .execute(tx)
+            .await?
+            .rows_affected())
+    }
+
+    /// Saves an Ethereum event to the database unless an event with the same transaction hash and log index
+    /// is already present.
+    /// It returns either the newly saved event model or the already persisted one.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn save_if_absent(
+        &self,
+        tx: &mut PgConnection,
+        model: NewModel<EthEventData>,
+    ) -> Result<PersistedEvent, CoreError> {
+        trace!("Saving event to the database if absent: {:?}", model);
+        let queries = self.repo.queries();
+        let sql = format!(
+            r#"
+            INSERT INTO {} ({}, {}, {}, {}) VALUES ($1, $2, $2, $3)
+            ON CONFLICT DO NOTHING
+            RETURNING {}
+        "#,
+            queries.qualified_table_name,
+            queries.version_field_name,
+            queries.create_epoch_millis_field_name,
+            queries.update_epoch_millis_field_name,
+            queries.data_field_name,
+            queries.id_field_name
+        );
+
+        let json_data = self.repo.codec().data_to_value(&model.data)?;
+        let create_epoch_millis = get_current_epoch_millis();
+
+        let row = ::sqlx::query(&sql)
+            .bind(model.version as i32)
+            .bind(create_epoch_millis)
+            .bind(json_data)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        match row {
+            Some(row) => {
+                let id: i64 = row.try_get(0)?;
+                Ok(PersistedEvent::New(EthEventModel {
+                    id: id as u64,
+                    version: model.version,
+                    data: model.data,
+                    create_epoch_millis,
+                    update_epoch_millis: create_epoch_millis,
+                }))
+            }
+            None => {
+                let existing = self
+                    .fetch_by_chain_position(tx, &model.data.transaction_hash, model.data.log_index)
+                    .await?
+                    .ok_or_else(|| CoreError::DatabaseError("Conflicting event not found".to_owned()))?;
+                Ok(PersistedEvent::Duplicate(existing))
+            }
+        }
+    }
+}
+
+/// A checkpoint repository that persists the ingestion progress of each token in the ETH_CHECKPOINT table
+/// of a Postgres database
+#[derive(Clone)]
+pub struct CheckpointRepository {
+    repo: SqlxPgC3p0Json<u64, CheckpointData, DefaultJsonCodec>,
+}
+
+impl Default for CheckpointRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CheckpointRepository {
+    /// Create a new CheckpointRepository
+    pub fn new() -> Self {
+        Self { repo: SqlxPgC3p0JsonBuilder::new("ETH_CHECKPOINT").build() }
+    }
+
+    /// Fetches the checkpoint of the given token, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_by_token(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+    ) -> Result<Option<CheckpointModel>, CoreError> {
+        trace!("Fetching the checkpoint of token {} from the database", token_address);
+        let sql = format!(
+            r#"
+            {}
+            where DATA ->> 'token_address' = $1
+        "#,
+            self.repo.queries().find_base_sql_query
+        );
+
+        Ok(self.repo.fetch_one_optional_with_sql(tx, ::sqlx::query(&sql).bind(format!("{token_address:#x}"))).await?)
+    }
+
+    /// Saves the checkpoint of a token, replacing the existing one if present.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn save_or_update(
+        &self,
+        tx: &mut PgConnection,
+        data: CheckpointData,
+    ) -> Result<CheckpointModel, CoreError> {
+        trace!("Saving checkpoint to the database: {:?}", data);
+        match self.fetch_by_token(tx, &data.token_address).await? {
+            Some(mut model) => {
+                model.data = data;
+                Ok(self.repo.update(tx, model).await?)
+            }
+            None => Ok(self.repo.save(tx, NewModel::new(data)).await?),
+        }
+    }
+
+    /// Moves the checkpoint of a token back to the given block, if it currently points to a later block.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn rollback_to(&self, tx: &mut PgConnection, data: CheckpointData) -> Result<(), CoreError> {
+        trace!("Rolling back checkpoint in the database: {:?}", data);
+        if let Some(mut model) = self.fetch_by_token(tx, &data.token_address).await? {
+            if model.data.block_number > data.block_number {
+                model.data = data;
+                self.repo.update(tx, model).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A token metadata repository that persists the metadata of each token in the ETH_TOKEN_METADATA table
+/// of a Postgres database
+#[derive(Clone)]
+pub struct TokenMetadataRepository {
+    repo: SqlxPgC3p0Json<u64, TokenMetadataData, DefaultJsonCodec>,
+}
+
+impl Default for TokenMetadataRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenMetadataRepository {
+    /// Create a new TokenMetadataRepository
+    pub fn new() -> Self {
+        Self { repo: SqlxPgC3p0JsonBuilder::new("ETH_TOKEN_METADATA").build() }
+    }
+
+    /// Fetches the metadata of all the tokens.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_all(&self, tx: &mut PgConnection) -> Result<Vec<TokenMetadataModel>, CoreError> {
+        trace!("Fetching the metadata of all the tokens from the database");
+        Ok(self.repo.fetch_all(tx).await?)
+    }
+
+    /// Fetches the metadata of the given token, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_by_token(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+    ) -> Result<Option<TokenMetadataModel>, CoreError> {
+        trace!("Fetching the metadata of token {} from the database", token_address);
+        let sql = format!(
+            r#"
+            {}
+            where DATA ->> 'token_address' = $1
+        "#,
+            self.repo.queries().find_base_sql_query
+        );
+
+        Ok(self.repo.fetch_one_optional_with_sql(tx, ::sqlx::query(&sql).bind(format!("{token_address:#x}"))).await?)
+    }
+
+    /// Saves the metadata of a token, replacing the existing one if present.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn save_or_update(
+        &self,
+        tx: &mut PgConnection,
+        data: TokenMetadataData,
+    ) -> Result<TokenMetadataModel, CoreError> {
+        trace!("Saving token metadata to the database: {:?}", data);
+        match self.fetch_by_token(tx, &data.token_address).await? {
+            Some(mut model) => {
+                model.data = data;
+                Ok(self.repo.update(tx, model).await?)
+            }
+            None => Ok(self.repo.save(tx, NewModel::new(data)).await?),
+        }
+    }
+}
+
+/// A balance repository that persists the balance of each address in the ETH_BALANCE table of a Postgres database
+#[derive(Clone)]
+pub struct BalanceRepository {
+    repo: SqlxPgC3p0Json<u64, BalanceData, DefaultJsonCodec>,
+}
+
+impl Default for BalanceRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BalanceRepository {
+    /// Create a new BalanceRepository
+    pub fn new() -> Self {
+        Self { repo: SqlxPgC3p0JsonBuilder::new("ETH_BALANCE").build() }
+    }
+
+    /// Fetches the balance of the given address for the given token, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_by_address(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+        address: &Address,
+    ) -> Result<Option<BalanceModel>, CoreError> {
+        trace!("Fetching the balance of address {} for token {} from the database", address, token_address);
+        let sql = format!(
+            r#"
+            {}
+            where DATA ->> 'token_address' = $1 and DATA ->> 'address' = $2
+        "#,
+            self.repo.queries().find_base_sql_query
+        );
+
+        Ok(self
+            .repo
+            .fetch_one_optional_with_sql(
+                tx,
+                ::sqlx::query(&sql).bind(format!("{token_address:#x}")).bind(format!("{address:#x}")),
+            )
+            .await?)
+    }
+
+    /// Fetches the `limit` addresses with the highest balance for the given token.
+    ///
+    /// The balances are sorted in descending order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_top_holders(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+        limit: u32,
+    ) -> Result<Vec<BalanceModel>, CoreError> {
+        trace!("Fetching the top {} holders of token {} from the database", limit, token_address);
+        let sql = format!(
+            r#"
+            {}
+            where DATA ->> 'token_address' = $1
+            order by (DATA ->> 'balance')::numeric desc, id asc
+            limit $2
+        "#,
+            self.repo.queries().find_base_sql_query
+        );
+
+        Ok(self
+            .repo
+            .fetch_all_with_sql(tx, ::sqlx::query(&sql).bind(format!("{token_address:#x}")).bind(limit as i64))
+            .await?)
+    }
+
+    /// Fetches the sum of the balances of all the addresses for the given token.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_total(&self, tx: &mut PgConnection, token_address: &Address) -> Result<I256, CoreError> {
+        trace!("Fetching the total balance of token {} from the database", token_address);
+        let sql = format!(
+            r#"
+            SELECT coalesce(sum((DATA ->> 'balance')::numeric), 0)::text FROM {}
+            where DATA ->> 'token_address' = $1
+        "#,
+            self.repo.queries().qualified_table_name
+        );
+
+        let row = ::sqlx::query(&sql).bind(format!("{token_address:#x}")).fetch_one(tx).await?;
+        let total: String = row.try_get(0)?;
+        I256::from_dec_str(&total).map_err(|err| CoreError::DatabaseError(format!("Invalid total balance: {err}")))
+    }
+
+    /// Adds the given amount, which can be negative, to the balance of an address.
+    /// The balance is created if not present.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database or the new balance overflows.
+    pub async fn add(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+        address: &Address,
+        amount: I256,
+    ) -> Result<BalanceModel, CoreError> {
+        trace!("Adding {} to the balance of address {} for token {} in the database", amount, address, token_address);
+        match self.fetch_by_address(tx, token_address, address).await? {
+            Some(mut model) => {
+                model.data.balance = model.data.balance.checked_add(amount).ok_or_else(|| {
+                    CoreError::BalanceOverflow(format!(
+                        "Adding {amount} to the balance {} of address {address} overflows",
+                        model.data.balance
+                    ))
+                })?;
+                Ok(self.repo.update(tx, model).await?)
+            }
+            None => {
+                let data = BalanceData { token_address: *token_address, address: *address, balance: amount };
+                Ok(self.repo.save(tx, NewModel::new(data)).await?)
+            }
+        }
+    }
+}
+
+/// An allowance repository that persists the allowance granted by each owner to each spender in the ETH_ALLOWANCE
+/// table of a Postgres database
+#[derive(Clone)]
+pub struct AllowanceRepository {
+    repo: SqlxPgC3p0Json<u64, AllowanceData, DefaultJsonCodec>,
+}
+
+impl Default for AllowanceRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AllowanceRepository {
+    /// Create a new AllowanceRepository
+    pub fn new() -> Self {
+        Self { repo: SqlxPgC3p0JsonBuilder::new("ETH_ALLOWANCE").build() }
+    }
+
+    /// Fetches the allowance granted by `owner` to `spender` for the given token, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_by_parties(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+        owner: &Address,
+        spender: &Address,
+    ) -> Result<Option<AllowanceModel>, CoreError> {
+        trace!(
+            "Fetching the allowance of token {} from owner {} to spender {} from the database",
+            token_address, owner, spender
+        );
+        let sql = format!(
+            r#"
+            {}
+            where DATA ->> 'token_address' = $1 and DATA ->> 'owner' = $2 and DATA ->> 'spender' = $3
+        "#,
+            self.repo.queries().find_base_sql_query
+        );
+
+        Ok(self
+            .repo
+            .fetch_one_optional_with_sql(
+                tx,
+                ::sqlx::query(&sql)
+                    .bind(format!("{token_address:#x}"))
+                    .bind(format!("{owner:#x}"))
+                    .bind(format!("{spender:#x}")),
+            )
+            .await?)
+    }
+
+    /// Fetches all the allowances granted by `owner`.
+    /// If a `token_address` is provided, only the allowances of that token are returned.
+    ///
+    /// The allowances are sorted in ascending order by `id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_by_owner(
+        &self,
+        tx: &mut PgConnection,
+        token_address: Option<&Address>,
+        owner: &Address,
+    ) -> Result<Vec<AllowanceModel>, CoreError> {
+        trace!("Fetching the allowances of owner {} from the database, token: {:?}", owner, token_address);
+        let sql = format!(
+            r#"
+            {}
+            where DATA ->> 'owner' = $1 and ($2::text is null or DATA ->> 'token_address' = $2)
+            order by id asc
+        "#,
+            self.repo.queries().find_base_sql_query
+        );
+
+        Ok(self
+            .repo
+            .fetch_all_with_sql(
+                tx,
+                ::sqlx::query(&sql)
+                    .bind(format!("{owner:#x}"))
+                    .bind(token_address.map(|address| format!("{address:#x}"))),
+            )
+            .await?)
+    }
+
+    /// Applies an allowance change of the given token.
+    /// An allowance is created when set; spending an allowance that is not present has no effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn apply(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+        change: AllowanceChange,
+    ) -> Result<(), CoreError> {
+        trace!("Applying allowance change of token {} in the database: {:?}", token_address, change);
+        let (owner, spender) = change.parties();
+        let existing = self.fetch_by_parties(tx, token_address, &owner, &spender).await?;
+        match (change, existing) {
+            (AllowanceChange::Set { allowance, .. }, Some(mut model)) => {
+                model.data.allowance = allowance;
+                self.repo.update(tx, model).await?;
+            }
+            (AllowanceChange::Set { allowance, .. }, None) => {
+                let data = AllowanceData { token_address: *token_address, owner, spender, allowance };
+                self.repo.save(tx, NewModel::new(data)).await?;
+            }
+            (AllowanceChange::Spend { amount, .. }, Some(mut model)) if model.data.allowance != U256::MAX => {
+                model.data.allowance = model.data.allowance.saturating_sub(amount);
+                self.repo.update(tx, model).await?;
+            }
+            (AllowanceChange::Spend { .. }, _) => {}
+        }
+        Ok(())
+    }
+
+    /// Deletes the allowance granted by `owner` to `spender` for the given token, if present.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn delete_by_parties(
+        &self,
+        tx: &mut PgConnection,
+        token_address: &Address,
+        owner: &Address,
+        spender: &Address,
+    ) -> Result<(), CoreError> {
+        trace!(
+            "Deleting the allowance of token {} from owner {} to spender {} from the database",
+            token_address, owner, spender
+        );
+        if let Some(model) = self.fetch_by_parties(tx, token_address, owner, spender).await? {
+            self.repo.delete(tx, model).await?;
+        }
+        Ok(())
+    }
+}
+
+/// A reconciliation repository that persists the outcome of each reconciliation in the ETH_RECONCILIATION table
+/// of a Postgres database
+#[derive(Clone)]
+pub struct ReconciliationRepository {
+    repo: SqlxPgC3p0Json<u64, ReconciliationData, DefaultJsonCodec>,
+}
+
+impl Default for ReconciliationRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReconciliationRepository {
+    /// Create a new ReconciliationRepository
+    pub fn new() -> Self {
+        Self { repo: SqlxPgC3p0JsonBuilder::new("ETH_RECONCILIATION").build() }
+    }
+
+    /// Fetches the latest reconciliation of each token.
+    ///
+    /// The reconciliations are sorted in ascending order by `id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_latest(&self, tx: &mut PgConnection) -> Result<Vec<ReconciliationModel>, CoreError> {
+        trace!("Fetching the latest reconciliation of each token from the database");
+        let queries = self.repo.queries();
+        let sql = format!(
+            r#"
+            {}
+            where id in (SELECT max(id) FROM {} group by DATA ->> 'token_address')
+            order by id asc
+        "#,
+            queries.find_base_sql_query, queries.qualified_table_name
+        );
+
+        Ok(self.repo.fetch_all_with_sql(tx, ::sqlx::query(&sql)).await?)
+    }
+
+    /// Saves the outcome of a reconciliation.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn save(
+        &self,
+        tx: &mut PgConnection,
+        data: ReconciliationData,
+    ) -> Result<ReconciliationModel, CoreError> {
+        trace!("Saving reconciliation to the database: {:?}", data);
+        Ok(self.repo.save(tx, NewModel::new(data)).await?)
     }
 }
@@ -1,22 +1,59 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use alloy::primitives::{Address, I256, U256};
 use c3p0::{sqlx::*, *};
 use log::*;
-use tokio::{sync::mpsc::UnboundedReceiver, task::JoinHandle};
+use tokio::{sync::mpsc::Receiver, task::JoinHandle, time::sleep};
 
-use crate::{error::CoreError, subscriber::model::Event};
-use ::sqlx::migrate::Migrator;
+use crate::{
+    backoff::Backoff,
+    error::CoreError,
+    subscriber::model::{Event, EventType, SubscriberMessage, TokenMetadata},
+};
+use ::sqlx::{PgConnection, migrate::Migrator};
 
 use super::{
-    model::{EthEventData, EthEventModel, EthEventType, EthEventTypeDiscriminants},
-    repository::EthEventRepository,
+    model::{
+        AllowanceModel, BalanceModel, CheckpointData, CheckpointModel, EthEventData, EthEventModel, EthEventParam,
+        EthEventType, EthEventTypeDiscriminants, EthTransactionData, LedgerSnapshot, PersistedEvent,
+        ReconciliationData, ReconciliationModel, StorageEvent, TokenMetadataData, TokenMetadataModel,
+    },
+    repository::{
+        AllowanceRepository, BalanceRepository, CheckpointRepository, EthEventRepository, ReconciliationRepository,
+        TokenMetadataRepository,
+    },
 };
 
 /// Migrator for the database. It allows to run migrations to automatically update the database.
 static MIGRATOR: Migrator = ::sqlx::migrate!("resources/db/pg/migrations");
 
+/// The maximum number of messages persisted in a single database transaction
+const MAX_BATCH_SIZE: usize = 100;
+
+/// The delay before the first retry of a batch that failed to be persisted
+const BATCH_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(100);
+
+/// The maximum delay between two retries of a batch that failed to be persisted
+const BATCH_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
 /// Service for persisting Ethereum events
 pub struct StorageService {
     pool: SqlxPgC3p0Pool,
     repo: EthEventRepository,
+    checkpoint_repo: CheckpointRepository,
+    metadata_repo: TokenMetadataRepository,
+    balance_repo: BalanceRepository,
+    allowance_repo: AllowanceRepository,
+    reconciliation_repo: ReconciliationRepository,
+    /// The number of messages waiting in the event stream
+    queue_depth: Arc<AtomicUsize>,
 }
 
 impl StorageService {
@@ -29,10 +66,19 @@ impl StorageService {
         MIGRATOR.run(pool.pool()).await?;
         info!("StorageService - Database migrations completed");
         info!("StorageService - New instance created");
-        Ok(Self { pool, repo: EthEventRepository::new() })
+        Ok(Self {
+            pool,
+            repo: EthEventRepository::new(),
+            checkpoint_repo: CheckpointRepository::new(),
+            metadata_repo: TokenMetadataRepository::new(),
+            balance_repo: BalanceRepository::new(),
+            allowance_repo: AllowanceRepository::new(),
+            reconciliation_repo: ReconciliationRepository::new(),
+            queue_depth: Default::default(),
+        })
     }
 
-    /// Fetches all Ethereum events from the storage, optionally filtered by event type.
+    /// Fetches all Ethereum events from the storage, optionally filtered by event type and token address.
     /// The events are sorted in ascending order by `id`.
     ///
     /// # Errors
@@ -41,6 +87,7 @@ impl StorageService {
     pub async fn fetch_all_events(
         &self,
         event_type: Option<EthEventTypeDiscriminants>,
+        token_address: Option<Address>,
         from_id: u64,
         limit: u32,
     ) -> Result<Vec<EthEventModel>, CoreError> {
@@ -48,68 +95,490 @@ impl StorageService {
         self.pool
             .transaction(async |tx| {
                 if let Some(event_type) = event_type {
-                    self.repo.fetch_all_by_type(tx, event_type, &from_id, limit).await
+                    self.repo.fetch_all_by_type(tx, event_type, token_address.as_ref(), &from_id, limit).await
                 } else {
-                    self.repo.fetch_all(tx, &from_id, limit).await
+                    self.repo.fetch_all(tx, token_address.as_ref(), &from_id, limit).await
                 }
             })
             .await
     }
 
-    /// Saves an Ethereum event to the storage.
+    /// Saves an Ethereum event to the storage, unless an event with the same transaction hash and log index
+    /// was already persisted.
     ///
-    /// If successful, it returns the saved event model populated with the generated id.
+    /// If successful, it returns either the new event model populated with the generated id
+    /// or the previously persisted one. The balances and the allowances are updated only when a new event
+    /// is persisted.
     ///
     /// # Errors
     ///
     /// Returns `Err` if there is an error interacting with the database.
-    pub async fn save_event(&self, model: EthEventData) -> Result<EthEventModel, CoreError> {
+    pub async fn save_event(&self, model: EthEventData) -> Result<PersistedEvent, CoreError> {
         debug!("StorageService - Saving event to the storage");
-        self.pool.transaction(async |tx| self.repo.save(tx, NewModel::new(model)).await).await
+        self.pool
+            .transaction(async |tx| {
+                let persisted = self.repo.save_if_absent(tx, NewModel::new(model)).await?;
+                if let PersistedEvent::New(model) = &persisted {
+                    apply_balance_changes(&self.balance_repo, tx, &model.data, false).await?;
+                    apply_allowance_change(&self.allowance_repo, tx, &model.data).await?;
+                }
+                Ok(persisted)
+            })
+            .await
+    }
+
+    /// Fetches the current balance of an address for the given token.
+    /// It returns zero if the address never appeared in a Deposit, Withdrawal or Transfer event.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_balance(&self, token_address: &Address, address: &Address) -> Result<I256, CoreError> {
+        debug!("StorageService - Fetching the balance of address {address} for token {token_address}");
+        let balance = self
+            .pool
+            .transaction(async |tx| self.balance_repo.fetch_by_address(tx, token_address, address).await)
+            .await?;
+        Ok(balance.map(|model| model.data.balance).unwrap_or_default())
+    }
+
+    /// Fetches the `limit` addresses with the highest balance for the given token,
+    /// sorted in descending order by balance.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_top_holders(&self, token_address: &Address, limit: u32) -> Result<Vec<BalanceModel>, CoreError> {
+        debug!("StorageService - Fetching the top {limit} holders of token {token_address}");
+        self.pool.transaction(async |tx| self.balance_repo.fetch_top_holders(tx, token_address, limit).await).await
+    }
+
+    /// Fetches the current allowances granted by an owner, optionally filtered by token address.
+    /// The allowances are derived from the Approval events and from the Transfer events initiated by the spender,
+    /// which are only recognized when the enrichment is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_allowances(
+        &self,
+        token_address: Option<Address>,
+        owner: &Address,
+    ) -> Result<Vec<AllowanceModel>, CoreError> {
+        debug!("StorageService - Fetching the allowances of owner {owner}");
+        self.pool
+            .transaction(async |tx| self.allowance_repo.fetch_by_owner(tx, token_address.as_ref(), owner).await)
+            .await
     }
 
-    /// Subscribes to an unbounded receiver of Ethereum events and saves them to the storage.
+    /// Fetches the balances of a token derived from the events persisted up to its checkpoint, if any.
+    /// The balances of the given `addresses` and of the `sample_size` top holders are returned, together with
+    /// the total supply.
+    ///
+    /// The events persisted after the checkpoint, which belong to blocks still being processed, are not counted.
+    /// The data is read from a single snapshot of the database, so that it is consistent with the checkpoint.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_ledger_snapshot(
+        &self,
+        token_address: &Address,
+        addresses: &[Address],
+        sample_size: u32,
+    ) -> Result<Option<LedgerSnapshot>, CoreError> {
+        debug!("StorageService - Fetching the ledger snapshot of token {token_address}");
+        self.pool
+            .transaction(async |tx| {
+                ::sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ").execute(&mut **tx).await?;
+
+                let Some(checkpoint) = self.checkpoint_repo.fetch_by_token(tx, token_address).await? else {
+                    return Ok(None);
+                };
+
+                let mut holders = addresses.to_vec();
+                for model in self.balance_repo.fetch_top_holders(tx, token_address, sample_size).await? {
+                    if !holders.contains(&model.data.address) {
+                        holders.push(model.data.address);
+                    }
+                }
+
+                let mut balances = Vec::with_capacity(holders.len());
+                for address in holders {
+                    let balance = self.balance_repo.fetch_by_address(tx, token_address, &address).await?;
+                    balances.push((address, balance.map(|model| model.data.balance).unwrap_or_default()));
+                }
+                let mut total_supply = self.balance_repo.fetch_total(tx, token_address).await?;
+
+                // Remove the changes of the events of the blocks following the checkpoint
+                let block_number = checkpoint.data.block_number;
+                // The events whose value does not fit in a balance never changed the ledger
+                for model in self.repo.fetch_from_block(tx, token_address, block_number + 1).await? {
+                    for (address, amount) in model.data.balance_changes().unwrap_or_default() {
+                        total_supply = checked_sub(total_supply, amount)?;
+                        if let Some((_, balance)) = balances.iter_mut().find(|(holder, _)| *holder == address) {
+                            *balance = checked_sub(*balance, amount)?;
+                        }
+                    }
+                }
+
+                Ok(Some(LedgerSnapshot {
+                    token_address: *token_address,
+                    block_number,
+                    block_hash: checkpoint.data.block_hash,
+                    balances,
+                    total_supply,
+                }))
+            })
+            .await
+    }
+
+    /// Saves the outcome of a reconciliation.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn save_reconciliation(&self, data: ReconciliationData) -> Result<ReconciliationModel, CoreError> {
+        debug!("StorageService - Saving the reconciliation of token {}", data.token_address);
+        self.pool.transaction(async |tx| self.reconciliation_repo.save(tx, data).await).await
+    }
+
+    /// Fetches the latest reconciliation of each token.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_latest_reconciliations(&self) -> Result<Vec<ReconciliationModel>, CoreError> {
+        debug!("StorageService - Fetching the latest reconciliations");
+        self.pool.transaction(async |tx| self.reconciliation_repo.fetch_latest(tx).await).await
+    }
+
+    /// Fetches the ingestion checkpoint of the given token, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_checkpoint(&self, token_address: &Address) -> Result<Option<CheckpointModel>, CoreError> {
+        debug!("StorageService - Fetching the checkpoint of token {token_address}");
+        self.pool.transaction(async |tx| self.checkpoint_repo.fetch_by_token(tx, token_address).await).await
+    }
+
+    /// Saves the metadata of a token, replacing the existing one if present.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn save_token_metadata(&self, metadata: TokenMetadata) -> Result<TokenMetadataModel, CoreError> {
+        debug!("StorageService - Saving the metadata of token {}", metadata.token_address);
+        let TokenMetadata { token_address, name, symbol, decimals } = metadata;
+        let data = TokenMetadataData { token_address, name, symbol, decimals };
+        self.pool.transaction(async |tx| self.metadata_repo.save_or_update(tx, data).await).await
+    }
+
+    /// Fetches the metadata of all the tokens.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if there is an error interacting with the database.
+    pub async fn fetch_all_token_metadata(&self) -> Result<Vec<TokenMetadataModel>, CoreError> {
+        debug!("StorageService - Fetching the metadata of all the tokens");
+        self.pool.transaction(async |tx| self.metadata_repo.fetch_all(tx).await).await
+    }
+
+    /// Returns the number of messages received by the event stream and waiting to be persisted,
+    /// as observed when the last batch was received or its persistence was last attempted
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth.load(Ordering::Relaxed)
+    }
+
+    /// Subscribes to a bounded receiver of subscriber messages and saves the Ethereum events to the storage.
     /// The function spawns a new tokio task that listens to the input stream for the events to be persisted.
     /// It returns the join handle of the spawned task and a receiver that can be used to receive the persisted events.
+    /// Each event is reported either as new or as a duplicate of an already persisted one.
+    ///
+    /// The response channel has the same capacity of the input one. While it is full, no further messages are
+    /// persisted, so that the backpressure propagates to the subscribers; if the response receiver is dropped,
+    /// the responses are discarded.
+    ///
+    /// The balances and the allowances affected by each new event are updated in the same transaction.
+    ///
+    /// When a [`SubscriberMessage::ChainReorganized`] message is received, the events from the reorganized block
+    /// onward are deleted, their balance changes reverted and the affected allowances rebuilt from the remaining
+    /// events, the checkpoint is moved back to the last valid block and a
+    /// [`StorageEvent::ChainReorganized`] notification is sent.
+    ///
+    /// The messages available in the channel are persisted in batches, each one in a single transaction.
+    /// If the transaction fails, e.g. because the database is unreachable, the same batch is retried with an
+    /// exponential backoff until it is committed; meanwhile no further message is read from the channel, so the
+    /// backpressure propagates to the subscribers and no message is ever skipped.
+    /// The checkpoint of a token is updated when a [`SubscriberMessage::BlockProcessed`] message is received,
+    /// atomically with the events that precede it in the same batch.
+    ///
+    /// The task terminates when all the senders are dropped, after every message already in the channel has been
+    /// persisted. A graceful shutdown therefore only needs to stop the subscribers and await the returned handle.
     pub fn subscribe_to_event_stream(
         &self,
-        mut receiver: UnboundedReceiver<Event>,
-    ) -> (UnboundedReceiver<EthEventModel>, JoinHandle<()>) {
+        mut receiver: Receiver<SubscriberMessage>,
+    ) -> (Receiver<StorageEvent>, JoinHandle<()>) {
         info!("StorageService - Subscribing to event stream");
 
         let pool = self.pool.clone();
         let repo = self.repo.clone();
-        let (response_tx, response_rx) = tokio::sync::mpsc::unbounded_channel();
+        let checkpoint_repo = self.checkpoint_repo.clone();
+        let balance_repo = self.balance_repo.clone();
+        let allowance_repo = self.allowance_repo.clone();
+        let queue_depth = self.queue_depth.clone();
+        let (response_tx, response_rx) = tokio::sync::mpsc::channel(receiver.max_capacity());
 
         let handle = tokio::spawn(async move {
-            while let Some(event) = receiver.recv().await {
-                let model = match event {
-                    Event::Approval { from, to, value } => {
-                        EthEventData { value, event_type: EthEventType::Approve { from, to } }
-                    }
-                    Event::Transfer { from, to, value } => {
-                        EthEventData { value, event_type: EthEventType::Transfer { from, to } }
-                    }
-                    Event::Deposit { to, value } => EthEventData { value, event_type: EthEventType::Deposit { to } },
-                    Event::Withdrawal { from, value } => {
-                        EthEventData { value, event_type: EthEventType::Withdrawal { from } }
-                    }
-                };
-                match pool.transaction(async |tx| repo.save(tx, NewModel::new(model)).await).await {
-                    Ok(event) => {
-                        trace!("Event persisted in the storage: {event:?}");
-                        if !response_tx.is_closed() {
-                            match response_tx.send(event) {
-                                Ok(()) => trace!("Response message sent"),
-                                Err(err) => error!("Failed to send response message: {err:?}"),
+            let mut messages = Vec::with_capacity(MAX_BATCH_SIZE);
+            while receiver.recv_many(&mut messages, MAX_BATCH_SIZE).await > 0 {
+                let batch_size = messages.len();
+                observe_queue_depth(&queue_depth, &receiver);
+
+                let mut backoff = Backoff::new(BATCH_RETRY_INITIAL_DELAY, BATCH_RETRY_MAX_DELAY, 0.0, None);
+                let responses = loop {
+                    let result = pool
+                        .transaction(async |tx| {
+                            let mut responses = Vec::with_capacity(batch_size);
+                            for message in messages.iter().cloned() {
+                                match message {
+                                    SubscriberMessage::Event(event) => {
+                                        let persisted =
+                                            repo.save_if_absent(tx, NewModel::new(to_event_data(event))).await?;
+                                        if let PersistedEvent::New(model) = &persisted {
+                                            apply_balance_changes(&balance_repo, tx, &model.data, false).await?;
+                                            apply_allowance_change(&allowance_repo, tx, &model.data).await?;
+                                        }
+                                        responses.push(StorageEvent::Persisted(persisted));
+                                    }
+                                    SubscriberMessage::BlockProcessed { token_address, block_number, block_hash } => {
+                                        let checkpoint = CheckpointData { token_address, block_number, block_hash };
+                                        checkpoint_repo.save_or_update(tx, checkpoint).await?;
+                                    }
+                                    SubscriberMessage::ChainReorganized {
+                                        token_address,
+                                        block_number,
+                                        parent_hash,
+                                    } => {
+                                        let mut allowances = HashSet::new();
+                                        for model in repo.fetch_from_block(tx, &token_address, block_number).await? {
+                                            apply_balance_changes(&balance_repo, tx, &model.data, true).await?;
+                                            if let Some(change) = model.data.allowance_change() {
+                                                allowances.insert(change.parties());
+                                            }
+                                        }
+                                        let removed_events =
+                                            repo.delete_from_block(tx, &token_address, block_number).await?;
+                                        for (owner, spender) in allowances {
+                                            rebuild_allowance(
+                                                &repo,
+                                                &allowance_repo,
+                                                tx,
+                                                &token_address,
+                                                &owner,
+                                                &spender,
+                                            )
+                                            .await?;
+                                        }
+                                        let checkpoint = CheckpointData {
+                                            token_address,
+                                            block_number: block_number.saturating_sub(1),
+                                            block_hash: parent_hash,
+                                        };
+                                        checkpoint_repo.rollback_to(tx, checkpoint).await?;
+                                        responses.push(StorageEvent::ChainReorganized {
+                                            token_address,
+                                            block_number,
+                                            removed_events,
+                                        });
+                                    }
+                                }
                             }
+                            Ok::<_, CoreError>(responses)
+                        })
+                        .await;
+
+                    match result {
+                        Ok(responses) => break responses,
+                        Err(err) => {
+                            // The batch is retried as is, so that the checkpoint never moves past a lost message
+                            let delay = backoff.next_delay().unwrap_or(BATCH_RETRY_MAX_DELAY);
+                            error!(
+                                "Failed to persist a batch of {batch_size} messages, attempt {}. Retrying in {} ms. Error: {err:?}",
+                                backoff.attempts(),
+                                delay.as_millis()
+                            );
+                            // The queue fills up while the database is unreachable
+                            observe_queue_depth(&queue_depth, &receiver);
+                            sleep(delay).await;
                         }
                     }
-                    Err(err) => error!("Failed to persist new event: {err:?}"),
                 };
+                messages.clear();
+
+                for response in responses {
+                    match &response {
+                        StorageEvent::Persisted(PersistedEvent::New(model)) => {
+                            trace!("Event persisted in the storage: {model:?}")
+                        }
+                        StorageEvent::Persisted(PersistedEvent::Duplicate(model)) => {
+                            debug!("Event already present in the storage with id {}", model.id)
+                        }
+                        StorageEvent::ChainReorganized { token_address, block_number, removed_events } => {
+                            warn!(
+                                "Chain reorganization of token {token_address}: removed {removed_events} events from block {block_number}"
+                            )
+                        }
+                    }
+                    if !response_tx.is_closed() {
+                        match response_tx.send(response).await {
+                            Ok(()) => trace!("Response message sent"),
+                            Err(err) => error!("Failed to send response message: {err:?}"),
+                        }
+                    }
+                }
             }
+            info!("StorageService - Event stream closed");
         });
 
         (response_rx, handle)
     }
 }
+
+/// Publishes the number of messages waiting in the event stream, warning when the stream is full
+fn observe_queue_depth(queue_depth: &AtomicUsize, receiver: &Receiver<SubscriberMessage>) {
+    let pending = receiver.len();
+    queue_depth.store(pending, Ordering::Relaxed);
+    if pending == receiver.max_capacity() {
+        warn!("The event stream is full: {pending} messages are waiting to be persisted");
+    }
+}
+
+/// Subtracts an amount from a balance of a ledger snapshot, failing on overflow
+fn checked_sub(balance: I256, amount: I256) -> Result<I256, CoreError> {
+    balance
+        .checked_sub(amount)
+        .ok_or_else(|| CoreError::BalanceOverflow(format!("Subtracting {amount} from the balance {balance} overflows")))
+}
+
+/// Applies the balance changes of an event, or reverts them if `revert` is true.
+///
+/// If a change would overflow a balance, none of the changes of the event is applied and a warning is logged:
+/// failing the transaction would stop the persistence of all the events because of a single hostile contract.
+async fn apply_balance_changes(
+    balance_repo: &BalanceRepository,
+    tx: &mut PgConnection,
+    event: &EthEventData,
+    revert: bool,
+) -> Result<(), CoreError> {
+    let skip = |err: CoreError| {
+        warn!(
+            "Skipping the balance changes of log {} of transaction {} of token {}: {err}",
+            event.log_index, event.transaction_hash, event.token_address
+        );
+        Ok(())
+    };
+    let changes = match event.balance_changes() {
+        Ok(changes) => changes,
+        Err(err) => return skip(err),
+    };
+
+    // The new balances are verified before any of them is written, so that the ledger is never partially updated
+    let mut amounts = Vec::with_capacity(changes.len());
+    for (address, amount) in changes {
+        let amount = if revert { amount.checked_neg() } else { Some(amount) };
+        let balance = balance_repo.fetch_by_address(tx, &event.token_address, &address).await?;
+        let balance = balance.map(|model| model.data.balance).unwrap_or_default();
+        match amount.filter(|amount| balance.checked_add(*amount).is_some()) {
+            Some(amount) => amounts.push((address, amount)),
+            None => {
+                return skip(CoreError::BalanceOverflow(format!(
+                    "The balance {balance} of address {address} overflows"
+                )));
+            }
+        }
+    }
+    for (address, amount) in amounts {
+        balance_repo.add(tx, &event.token_address, &address, amount).await?;
+    }
+    Ok(())
+}
+
+/// Applies the allowance change of an event, if any
+async fn apply_allowance_change(
+    allowance_repo: &AllowanceRepository,
+    tx: &mut PgConnection,
+    event: &EthEventData,
+) -> Result<(), CoreError> {
+    if let Some(change) = event.allowance_change() {
+        allowance_repo.apply(tx, &event.token_address, change).await?;
+    }
+    Ok(())
+}
+
+/// Rebuilds the allowance granted by `owner` to `spender` by replaying the persisted events that changed it.
+/// Unlike the balances, the allowances cannot be reverted event by event because an Approval overwrites them.
+async fn rebuild_allowance(
+    repo: &EthEventRepository,
+    allowance_repo: &AllowanceRepository,
+    tx: &mut PgConnection,
+    token_address: &Address,
+    owner: &Address,
+    spender: &Address,
+) -> Result<(), CoreError> {
+    allowance_repo.delete_by_parties(tx, token_address, owner, spender).await?;
+    for model in repo.fetch_allowance_events(tx, token_address, owner, spender).await? {
+        apply_allowance_change(allowance_repo, tx, &model.data).await?;
+    }
+    Ok(())
+}
+
+/// Converts an event received from the subscriber into the persisted event data
+fn to_event_data(event: Event) -> EthEventData {
+    let (value, event_type) = match event.event_type {
+        EventType::Approval { from, to, value } => (value, EthEventType::Approve { from, to }),
+        EventType::Transfer { from, to, value } => (value, EthEventType::Transfer { from, to }),
+        EventType::Deposit { to, value } => (value, EthEventType::Deposit { to }),
+        EventType::Withdrawal { from, value } => (value, EthEventType::Withdrawal { from }),
+        EventType::NftApproval { owner, approved, token_id } => {
+            (U256::ZERO, EthEventType::NftApproval { owner, approved, token_id })
+        }
+        EventType::NftTransfer { from, to, token_id } => (U256::ZERO, EthEventType::NftTransfer { from, to, token_id }),
+        EventType::ApprovalForAll { owner, operator, approved } => {
+            (U256::ZERO, EthEventType::ApprovalForAll { owner, operator, approved })
+        }
+        EventType::TransferSingle { operator, from, to, id, value } => {
+            (value, EthEventType::TransferSingle { operator, from, to, id })
+        }
+        EventType::TransferBatch { operator, from, to, ids, values } => {
+            (U256::ZERO, EthEventType::TransferBatch { operator, from, to, ids, values })
+        }
+        EventType::Uri { id, value } => (U256::ZERO, EthEventType::Uri { id, value }),
+        EventType::Generic { name, params } => {
+            let params = params
+                .into_iter()
+                .map(|param| EthEventParam { name: param.name, ty: param.ty, value: param.value })
+                .collect();
+            (U256::ZERO, EthEventType::Generic { name, params })
+        }
+    };
+    EthEventData {
+        token_address: event.token_address,
+        value,
+        event_type,
+        block_number: event.block_number,
+        block_hash: event.block_hash,
+        block_timestamp: event.block_timestamp,
+        transaction_hash: event.transaction_hash,
+        transaction_index: event.transaction_index,
+        log_index: event.log_index,
+        transaction: event.transaction.map(|transaction| EthTransactionData {
+            from: transaction.from,
+            to: transaction.to,
+            gas_used: transaction.gas_used,
+            effective_gas_price: transaction.effective_gas_price,
+            status: transaction.status,
+        }),
+    }
+}
@@ -0,0 +1,126 @@
+use super::model::{Finality, SubscriberMessage, UnconfirmedMessages};
+
+/// Buffers the messages of a subscription until their block is confirmed.
+///
+/// In [`Finality::Latest`] mode a block is confirmed when `confirmations` blocks were mined on top of it,
+/// while in [`Finality::Safe`] and [`Finality::Finalized`] modes it is confirmed when covered by the
+/// corresponding block tag.
+pub struct ConfirmationBuffer {
+    finality: Finality,
+    confirmations: u64,
+    pending: UnconfirmedMessages,
+    /// The last block known to be confirmed
+    confirmed_block: Option<u64>,
+}
+
+impl ConfirmationBuffer {
+    /// Creates a new `ConfirmationBuffer` that stores the unconfirmed messages in `pending`
+    pub fn new(finality: Finality, confirmations: u64, pending: UnconfirmedMessages) -> Self {
+        Self { finality, confirmations, pending, confirmed_block: None }
+    }
+
+    /// Returns true if the messages need to wait for the confirmation of their block
+    pub fn is_enabled(&self) -> bool {
+        self.finality != Finality::Latest || self.confirmations > 0
+    }
+
+    /// Returns the finality mode
+    pub fn finality(&self) -> Finality {
+        self.finality
+    }
+
+    /// Returns the last confirmed block given the current head of the chain in [`Finality::Latest`] mode
+    pub fn confirmed_block_at(&self, head: u64) -> u64 {
+        head.saturating_sub(self.confirmations)
+    }
+
+    /// Adds a message of the given block to the buffer.
+    /// The message is returned back if it can be forwarded immediately.
+    pub fn push(&mut self, block_number: u64, message: SubscriberMessage) -> Option<SubscriberMessage> {
+        if !self.is_enabled() || self.confirmed_block.is_some_and(|confirmed| block_number <= confirmed) {
+            return Some(message);
+        }
+        self.pending.lock().unwrap().push_back((block_number, message));
+        None
+    }
+
+    /// Marks the blocks up to `confirmed_block` as confirmed and returns their buffered messages in chain order
+    pub fn release(&mut self, confirmed_block: u64) -> Vec<SubscriberMessage> {
+        self.confirmed_block = Some(self.confirmed_block.map_or(confirmed_block, |c| c.max(confirmed_block)));
+
+        let mut pending = self.pending.lock().unwrap();
+        let mut released = vec![];
+        while pending.front().is_some_and(|(block_number, _)| *block_number <= confirmed_block) {
+            if let Some((_, message)) = pending.pop_front() {
+                released.push(message);
+            }
+        }
+        released
+    }
+
+    /// Discards the buffered messages from `block_number` onward because of a chain reorganization.
+    /// It returns true if confirmed blocks were affected and the reorganization must be forwarded.
+    pub fn rollback(&mut self, block_number: u64) -> bool {
+        self.pending.lock().unwrap().retain(|(message_block, _)| *message_block < block_number);
+        !self.is_enabled() || self.confirmed_block.is_some_and(|confirmed| block_number <= confirmed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use alloy::primitives::{Address, B256};
+
+    use super::*;
+
+    fn block_processed(block_number: u64) -> SubscriberMessage {
+        SubscriberMessage::BlockProcessed { token_address: Address::ZERO, block_number, block_hash: B256::ZERO }
+    }
+
+    #[test]
+    fn should_forward_immediately_when_disabled() {
+        // Arrange
+        let mut buffer = ConfirmationBuffer::new(Finality::Latest, 0, Default::default());
+
+        // Assert
+        assert!(!buffer.is_enabled());
+        assert_eq!(Some(block_processed(10)), buffer.push(10, block_processed(10)));
+    }
+
+    #[test]
+    fn should_release_confirmed_messages_in_order() {
+        // Arrange
+        let pending = UnconfirmedMessages::default();
+        let mut buffer = ConfirmationBuffer::new(Finality::Latest, 2, pending.clone());
+
+        // Act
+        for block_number in 10..15 {
+            assert_eq!(None, buffer.push(block_number, block_processed(block_number)));
+        }
+        let released = buffer.release(buffer.confirmed_block_at(14));
+
+        // Assert
+        assert_eq!(vec![block_processed(10), block_processed(11), block_processed(12)], released);
+        assert_eq!(2, pending.lock().unwrap().len());
+
+        // Messages of already confirmed blocks are forwarded immediately
+        assert_eq!(Some(block_processed(12)), buffer.push(12, block_processed(12)));
+    }
+
+    #[test]
+    fn should_forward_reorganizations_of_confirmed_blocks_only() {
+        // Arrange
+        let pending = UnconfirmedMessages::default();
+        let mut buffer = ConfirmationBuffer::new(Finality::Finalized, 0, pending.clone());
+        for block_number in 10..15 {
+            buffer.push(block_number, block_processed(block_number));
+        }
+        buffer.release(11);
+
+        // Act & Assert
+        assert!(!buffer.rollback(13));
+        assert_eq!(1, pending.lock().unwrap().len());
+        assert!(buffer.rollback(11));
+        assert!(pending.lock().unwrap().is_empty());
+    }
+}
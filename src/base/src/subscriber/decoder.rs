@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+
+use alloy::{
+    dyn_abi::{DynSolValue, EventExt},
+    json_abi::{Event as AbiEvent, JsonAbi},
+    primitives::{B256, hex},
+    rpc::types::Log,
+    sol,
+    sol_types::SolEvent,
+};
+use log::*;
+
+use super::model::{EventParam, EventType, TokenStandard};
+
+// Codegen from ABI files to interact with the contracts.
+sol!(
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    IERC20,
+    "resources/abi/IERC20.json"
+);
+
+sol!(
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    IWETH9,
+    "resources/abi/IWETH9.json"
+);
+
+sol!(
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    IERC721,
+    "resources/abi/IERC721.json"
+);
+
+sol!(
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    IERC1155,
+    "resources/abi/IERC1155.json"
+);
+
+/// Decoder of the logs emitted by a token contract.
+///
+/// The events of the token standard are decoded with the compile-time bindings, while the other events
+/// declared by the optional runtime-loaded ABI are decoded dynamically.
+pub struct LogDecoder {
+    standard: TokenStandard,
+    /// The non-anonymous events of the runtime-loaded ABI, by selector
+    abi_events: HashMap<B256, Vec<AbiEvent>>,
+}
+
+impl LogDecoder {
+    /// Creates a new decoder for the given token standard, loading the events of the JSON ABI file at `abi_path`
+    /// if provided.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ABI file cannot be read or parsed.
+    pub fn new(standard: TokenStandard, abi_path: Option<&str>) -> anyhow::Result<Self> {
+        let mut abi_events: HashMap<B256, Vec<AbiEvent>> = HashMap::new();
+        if let Some(abi_path) = abi_path {
+            let abi: JsonAbi = serde_json::from_str(&std::fs::read_to_string(abi_path)?)?;
+            for event in abi.events().filter(|event| !event.anonymous) {
+                abi_events.entry(event.selector()).or_default().push(event.clone());
+            }
+            info!("Loaded {} events from the ABI file {abi_path}", abi_events.len());
+        }
+        Ok(Self { standard, abi_events })
+    }
+
+    /// Decodes a log emitted by the token contract.
+    /// Returns `None` if the log does not match any of the known events.
+    pub fn decode(&self, log: &Log) -> anyhow::Result<Option<EventType>> {
+        let event_type = match self.standard {
+            TokenStandard::Erc20 => decode_erc20_log(log)?,
+            TokenStandard::Weth => decode_weth_log(log)?,
+            TokenStandard::Erc721 => decode_erc721_log(log)?,
+            TokenStandard::Erc1155 => decode_erc1155_log(log)?,
+        };
+        if event_type.is_some() {
+            return Ok(event_type);
+        }
+
+        let event_type = self.decode_abi_log(log)?;
+        if event_type.is_none() {
+            warn!("Received unknown event: {:?}", log.topic0());
+        }
+        Ok(event_type)
+    }
+
+    /// Decodes a log with the events of the runtime-loaded ABI.
+    /// Events sharing the same selector are told apart by the number of their indexed parameters.
+    fn decode_abi_log(&self, log: &Log) -> anyhow::Result<Option<EventType>> {
+        let Some(event) = log
+            .topic0()
+            .and_then(|selector| self.abi_events.get(selector))
+            .and_then(|events| events.iter().find(|event| event.num_topics() == log.topics().len()))
+        else {
+            return Ok(None);
+        };
+
+        let decoded = event.decode_log(log.data())?;
+        let mut indexed = decoded.indexed.into_iter();
+        let mut body = decoded.body.into_iter();
+
+        let mut params = Vec::with_capacity(event.inputs.len());
+        for input in &event.inputs {
+            let value = if input.indexed { indexed.next() } else { body.next() };
+            let value = value.ok_or_else(|| anyhow::anyhow!("Missing value of parameter {}", input.name))?;
+            params.push(EventParam {
+                name: input.name.clone(),
+                ty: input.selector_type().into_owned(),
+                value: to_json(value),
+            });
+        }
+
+        debug!("Received event from subscription: {} with {} parameters", event.name, params.len());
+        Ok(Some(EventType::Generic { name: event.name.clone(), params }))
+    }
+}
+
+/// Converts a decoded ABI value into JSON.
+/// Integers are converted to decimal strings to preserve their precision, bytes and addresses to hex strings.
+fn to_json(value: DynSolValue) -> serde_json::Value {
+    match value {
+        DynSolValue::Bool(value) => value.into(),
+        DynSolValue::Int(value, _) => value.to_string().into(),
+        DynSolValue::Uint(value, _) => value.to_string().into(),
+        DynSolValue::FixedBytes(value, size) => hex::encode_prefixed(&value[..size]).into(),
+        DynSolValue::Address(value) => format!("{value:#x}").into(),
+        DynSolValue::Function(value) => format!("{value:#x}").into(),
+        DynSolValue::Bytes(value) => hex::encode_prefixed(value).into(),
+        DynSolValue::String(value) => value.into(),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) | DynSolValue::Tuple(values) => {
+            values.into_iter().map(to_json).collect()
+        }
+    }
+}
+
+/// Returns the signature hash of the named event of the given token standard, together with the indexes
+/// of the topics holding its `from` and `to` participants.
+/// Returns `None` if the event is not declared by the standard.
+pub fn participant_topics(standard: TokenStandard, event: &str) -> Option<(B256, Option<usize>, Option<usize>)> {
+    use TokenStandard::*;
+    let topics = match (standard, event) {
+        (Erc20 | Weth | Erc721, "Transfer") => (IERC20::Transfer::SIGNATURE_HASH, Some(1), Some(2)),
+        (Erc20 | Weth | Erc721, "Approval") => (IERC20::Approval::SIGNATURE_HASH, Some(1), Some(2)),
+        (Weth, "Deposit") => (IWETH9::Deposit::SIGNATURE_HASH, None, Some(1)),
+        (Weth, "Withdrawal") => (IWETH9::Withdrawal::SIGNATURE_HASH, Some(1), None),
+        (Erc721 | Erc1155, "ApprovalForAll") => (IERC721::ApprovalForAll::SIGNATURE_HASH, Some(1), Some(2)),
+        (Erc1155, "TransferSingle") => (IERC1155::TransferSingle::SIGNATURE_HASH, Some(2), Some(3)),
+        (Erc1155, "TransferBatch") => (IERC1155::TransferBatch::SIGNATURE_HASH, Some(2), Some(3)),
+        _ => return None,
+    };
+    Some(topics)
+}
+
+/// Decodes a log emitted by a standard ERC20 contract.
+///
+/// The ERC721 `Transfer` and `Approval` events share the signature of the ERC20 ones, but they index the token id
+/// too; they are told apart by the number of topics.
+fn decode_erc20_log(log: &Log) -> anyhow::Result<Option<EventType>> {
+    let event_type = match log.topic0() {
+        // Match the `Approval(address,address,uint256)` event.
+        Some(&IERC20::Approval::SIGNATURE_HASH) if log.topics().len() == 3 => {
+            let IERC20::Approval { owner, spender, value } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: Approval from {owner} to {spender} of value {value}");
+            EventType::Approval { from: owner, to: spender, value }
+        }
+
+        // Match the `Transfer(address,address,uint256)` event.
+        Some(&IERC20::Transfer::SIGNATURE_HASH) if log.topics().len() == 3 => {
+            let IERC20::Transfer { from, to, value } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: Transfer from {from} to {to} of value {value}");
+            EventType::Transfer { from, to, value }
+        }
+
+        _ => return Ok(None),
+    };
+    Ok(Some(event_type))
+}
+
+/// Decodes a log emitted by a WETH contract.
+/// The ERC20 events are delegated to the standard ERC20 decoder.
+fn decode_weth_log(log: &Log) -> anyhow::Result<Option<EventType>> {
+    let event_type = match log.topic0() {
+        // Match the `Deposit(address,uint256)` event.
+        Some(&IWETH9::Deposit::SIGNATURE_HASH) => {
+            let IWETH9::Deposit { dst, wad } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: Deposit to {dst} of value {wad}");
+            EventType::Deposit { to: dst, value: wad }
+        }
+
+        // Match the `Withdrawal(address,uint256)` event.
+        Some(&IWETH9::Withdrawal::SIGNATURE_HASH) => {
+            let IWETH9::Withdrawal { src, wad } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: Withdrawal from {src} of value {wad}");
+            EventType::Withdrawal { from: src, value: wad }
+        }
+
+        _ => return decode_erc20_log(log),
+    };
+    Ok(Some(event_type))
+}
+
+/// Decodes a log emitted by an ERC721 contract.
+/// The `Transfer` and `Approval` events are matched only if the token id is indexed, see [`decode_erc20_log`].
+fn decode_erc721_log(log: &Log) -> anyhow::Result<Option<EventType>> {
+    let event_type = match log.topic0() {
+        // Match the `Approval(address,address,uint256)` event.
+        Some(&IERC721::Approval::SIGNATURE_HASH) if log.topics().len() == 4 => {
+            let IERC721::Approval { owner, approved, tokenId } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: Approval from {owner} to {approved} of token {tokenId}");
+            EventType::NftApproval { owner, approved, token_id: tokenId }
+        }
+
+        // Match the `ApprovalForAll(address,address,bool)` event.
+        Some(&IERC721::ApprovalForAll::SIGNATURE_HASH) => {
+            let IERC721::ApprovalForAll { owner, operator, approved } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: ApprovalForAll from {owner} to {operator}: {approved}");
+            EventType::ApprovalForAll { owner, operator, approved }
+        }
+
+        // Match the `Transfer(address,address,uint256)` event.
+        Some(&IERC721::Transfer::SIGNATURE_HASH) if log.topics().len() == 4 => {
+            let IERC721::Transfer { from, to, tokenId } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: Transfer from {from} to {to} of token {tokenId}");
+            EventType::NftTransfer { from, to, token_id: tokenId }
+        }
+
+        _ => return Ok(None),
+    };
+    Ok(Some(event_type))
+}
+
+/// Decodes a log emitted by an ERC1155 contract.
+fn decode_erc1155_log(log: &Log) -> anyhow::Result<Option<EventType>> {
+    let event_type = match log.topic0() {
+        // Match the `ApprovalForAll(address,address,bool)` event.
+        Some(&IERC1155::ApprovalForAll::SIGNATURE_HASH) => {
+            let IERC1155::ApprovalForAll { account, operator, approved } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: ApprovalForAll from {account} to {operator}: {approved}");
+            EventType::ApprovalForAll { owner: account, operator, approved }
+        }
+
+        // Match the `TransferSingle(address,address,address,uint256,uint256)` event.
+        Some(&IERC1155::TransferSingle::SIGNATURE_HASH) => {
+            let IERC1155::TransferSingle { operator, from, to, id, value } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: TransferSingle from {from} to {to} of {value} of token {id}");
+            EventType::TransferSingle { operator, from, to, id, value }
+        }
+
+        // Match the `TransferBatch(address,address,address,uint256[],uint256[])` event.
+        Some(&IERC1155::TransferBatch::SIGNATURE_HASH) => {
+            let IERC1155::TransferBatch { operator, from, to, ids, values } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: TransferBatch from {from} to {to} of {} tokens", ids.len());
+            EventType::TransferBatch { operator, from, to, ids, values }
+        }
+
+        // Match the `URI(string,uint256)` event.
+        Some(&IERC1155::URI::SIGNATURE_HASH) => {
+            let IERC1155::URI { value, id } = log.log_decode()?.inner.data;
+            debug!("Received event from subscription: URI of token {id} set to {value}");
+            EventType::Uri { id, value }
+        }
+
+        _ => return Ok(None),
+    };
+    Ok(Some(event_type))
+}
+
+#[cfg(test)]
+mod test {
+
+    use alloy::primitives::{Address, U256};
+
+    use super::*;
+
+    /// Builds an RPC log emitted by a random contract from the given event
+    fn to_log<E: SolEvent>(event: &E) -> Log {
+        Log {
+            inner: alloy::primitives::Log { address: Address::random(), data: event.encode_log_data() },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_decode_erc20_events() {
+        // Arrange
+        let (from, to, value) = (Address::random(), Address::random(), U256::from(1234));
+
+        let erc20 = LogDecoder::new(TokenStandard::Erc20, None).unwrap();
+
+        // Act
+        let transfer = erc20.decode(&to_log(&IERC20::Transfer { from, to, value })).unwrap();
+        let approval = erc20.decode(&to_log(&IERC20::Approval { owner: from, spender: to, value })).unwrap();
+
+        // Assert
+        assert_eq!(Some(EventType::Transfer { from, to, value }), transfer);
+        assert_eq!(Some(EventType::Approval { from, to, value }), approval);
+    }
+
+    #[test]
+    fn should_decode_weth_events() {
+        // Arrange
+        let (from, to, value) = (Address::random(), Address::random(), U256::from(1234));
+
+        let weth = LogDecoder::new(TokenStandard::Weth, None).unwrap();
+
+        // Act
+        let transfer = weth.decode(&to_log(&IWETH9::Transfer { src: from, dst: to, wad: value })).unwrap();
+        let deposit = weth.decode(&to_log(&IWETH9::Deposit { dst: to, wad: value })).unwrap();
+        let withdrawal = weth.decode(&to_log(&IWETH9::Withdrawal { src: from, wad: value })).unwrap();
+
+        // Assert
+        assert_eq!(Some(EventType::Transfer { from, to, value }), transfer);
+        assert_eq!(Some(EventType::Deposit { to, value }), deposit);
+        assert_eq!(Some(EventType::Withdrawal { from, value }), withdrawal);
+    }
+
+    #[test]
+    fn should_ignore_weth_events_of_erc20_tokens() {
+        // Arrange
+        let erc20 = LogDecoder::new(TokenStandard::Erc20, None).unwrap();
+        let log = to_log(&IWETH9::Deposit { dst: Address::random(), wad: U256::from(1234) });
+
+        // Act
+        let event = erc20.decode(&log).unwrap();
+
+        // Assert
+        assert_eq!(None, event);
+    }
+
+    #[test]
+    fn should_tell_apart_erc721_and_erc20_transfers() {
+        // Arrange
+        let (from, to, value) = (Address::random(), Address::random(), U256::from(1234));
+        let erc20_log = to_log(&IERC20::Transfer { from, to, value });
+        let erc721_log = to_log(&IERC721::Transfer { from, to, tokenId: value });
+        let erc20 = LogDecoder::new(TokenStandard::Erc20, None).unwrap();
+        let erc721 = LogDecoder::new(TokenStandard::Erc721, None).unwrap();
+
+        // Act & Assert
+        assert_eq!(erc20_log.topic0(), erc721_log.topic0());
+        assert_eq!(Some(EventType::Transfer { from, to, value }), erc20.decode(&erc20_log).unwrap());
+        assert_eq!(None, erc20.decode(&erc721_log).unwrap());
+        assert_eq!(Some(EventType::NftTransfer { from, to, token_id: value }), erc721.decode(&erc721_log).unwrap());
+        assert_eq!(None, erc721.decode(&erc20_log).unwrap());
+    }
+
+    #[test]
+    fn should_decode_erc721_events() {
+        // Arrange
+        let (owner, operator, token_id) = (Address::random(), Address::random(), U256::from(1234));
+        let erc721 = LogDecoder::new(TokenStandard::Erc721, None).unwrap();
+
+        // Act
+        let approval =
+            erc721.decode(&to_log(&IERC721::Approval { owner, approved: operator, tokenId: token_id })).unwrap();
+        let approval_for_all =
+            erc721.decode(&to_log(&IERC721::ApprovalForAll { owner, operator, approved: true })).unwrap();
+
+        // Assert
+        assert_eq!(Some(EventType::NftApproval { owner, approved: operator, token_id }), approval);
+        assert_eq!(Some(EventType::ApprovalForAll { owner, operator, approved: true }), approval_for_all);
+    }
+
+    #[test]
+    fn should_decode_erc1155_events() {
+        // Arrange
+        let (operator, from, to) = (Address::random(), Address::random(), Address::random());
+        let (ids, values) = (vec![U256::from(1), U256::from(2)], vec![U256::from(10), U256::from(20)]);
+        let erc1155 = LogDecoder::new(TokenStandard::Erc1155, None).unwrap();
+
+        // Act
+        let single = erc1155
+            .decode(&to_log(&IERC1155::TransferSingle { operator, from, to, id: ids[0], value: values[0] }))
+            .unwrap();
+        let batch = erc1155
+            .decode(&to_log(&IERC1155::TransferBatch { operator, from, to, ids: ids.clone(), values: values.clone() }))
+            .unwrap();
+        let uri = erc1155.decode(&to_log(&IERC1155::URI { value: "ipfs://uri".to_owned(), id: ids[1] })).unwrap();
+
+        // Assert
+        assert_eq!(Some(EventType::TransferSingle { operator, from, to, id: ids[0], value: values[0] }), single);
+        assert_eq!(Some(EventType::TransferBatch { operator, from, to, ids: ids.clone(), values }), batch);
+        assert_eq!(Some(EventType::Uri { id: ids[1], value: "ipfs://uri".to_owned() }), uri);
+    }
+
+    #[test]
+    fn should_decode_events_declared_by_the_abi_file() {
+        // Arrange
+        let decoder = LogDecoder::new(TokenStandard::Erc20, Some("resources/abi/IWETH9.json")).unwrap();
+        let (dst, wad) = (Address::random(), U256::MAX);
+
+        // Act
+        let deposit = decoder.decode(&to_log(&IWETH9::Deposit { dst, wad })).unwrap();
+        let transfer = decoder.decode(&to_log(&IWETH9::Transfer { src: dst, dst, wad })).unwrap();
+
+        // Assert
+        assert_eq!(
+            Some(EventType::Generic {
+                name: "Deposit".to_owned(),
+                params: vec![
+                    EventParam { name: "dst".to_owned(), ty: "address".to_owned(), value: format!("{dst:#x}").into() },
+                    EventParam { name: "wad".to_owned(), ty: "uint256".to_owned(), value: wad.to_string().into() },
+                ],
+            }),
+            deposit
+        );
+        // the events of the token standard keep their typed representation
+        assert_eq!(Some(EventType::Transfer { from: dst, to: dst, value: wad }), transfer);
+    }
+
+    #[test]
+    fn should_fail_if_the_abi_file_does_not_exist() {
+        assert!(LogDecoder::new(TokenStandard::Erc20, Some("resources/abi/missing.json")).is_err());
+    }
+
+    #[test]
+    fn should_return_the_topics_of_the_participants() {
+        // Arrange
+        let (operator, from, to) = (Address::random(), Address::random(), Address::random());
+        let transfer = to_log(&IERC20::Transfer { from, to, value: U256::from(1) });
+        let deposit = to_log(&IWETH9::Deposit { dst: to, wad: U256::from(1) });
+        let transfer_single =
+            to_log(&IERC1155::TransferSingle { operator, from, to, id: U256::from(1), value: U256::from(1) });
+
+        // Act & Assert
+        for (standard, event, log) in [
+            (TokenStandard::Erc20, "Transfer", transfer),
+            (TokenStandard::Weth, "Deposit", deposit),
+            (TokenStandard::Erc1155, "TransferSingle", transfer_single),
+        ] {
+            let (signature, from_index, to_index) = participant_topics(standard, event).unwrap();
+            assert_eq!(Some(&signature), log.topic0());
+            if let Some(from_index) = from_index {
+                assert_eq!(from.into_word(), log.topics()[from_index]);
+            }
+            assert_eq!(to.into_word(), log.topics()[to_index.unwrap()]);
+        }
+
+        assert!(participant_topics(TokenStandard::Erc20, "Deposit").is_none());
+        assert!(participant_topics(TokenStandard::Erc1155, "Transfer").is_none());
+    }
+}
@@ -1,2 +1,7 @@
+mod confirmation;
+pub(crate) mod decoder;
 pub mod model;
+mod node;
+pub mod replay;
 pub mod service;
+mod tracker;
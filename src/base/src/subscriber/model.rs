@@ -1,11 +1,257 @@
-use alloy::primitives::{Address, U256};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use alloy::primitives::{Address, B256, U256};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+/// Ethereum event decoded from a log, together with its position in the chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Event {
+    /// The address of the contract that emitted the event
+    pub token_address: Address,
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// The block timestamp in seconds since the Unix epoch
+    pub block_timestamp: u64,
+    pub transaction_hash: B256,
+    pub transaction_index: u64,
+    pub log_index: u64,
+    pub event_type: EventType,
+    /// The details of the transaction that emitted the event, set by the optional enrichment stage
+    pub transaction: Option<TransactionInfo>,
+}
+
+/// The details of a mined transaction, taken from its receipt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransactionInfo {
+    /// The account that initiated the transaction
+    pub from: Address,
+    /// The recipient of the transaction, `None` for a contract creation
+    pub to: Option<Address>,
+    pub gas_used: u64,
+    /// The price per unit of gas paid by the transaction, in wei
+    pub effective_gas_price: u128,
+    /// True if the transaction succeeded
+    pub status: bool,
+}
 
 /// Ethereum event type.
-/// This matches the events emitted by the IWETH9 contract
+/// This matches the events emitted by the standard ERC20, WETH, ERC721 and ERC1155 contracts,
+/// while the events declared by a runtime-loaded ABI are decoded as `Generic`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum EventType {
+    Approval {
+        from: Address,
+        to: Address,
+        value: U256,
+    },
+    Transfer {
+        from: Address,
+        to: Address,
+        value: U256,
+    },
+    Deposit {
+        to: Address,
+        value: U256,
+    },
+    Withdrawal {
+        from: Address,
+        value: U256,
+    },
+    /// ERC721 `Approval` event
+    NftApproval {
+        owner: Address,
+        approved: Address,
+        token_id: U256,
+    },
+    /// ERC721 `Transfer` event
+    NftTransfer {
+        from: Address,
+        to: Address,
+        token_id: U256,
+    },
+    /// ERC721 and ERC1155 `ApprovalForAll` event
+    ApprovalForAll {
+        owner: Address,
+        operator: Address,
+        approved: bool,
+    },
+    /// ERC1155 `TransferSingle` event
+    TransferSingle {
+        operator: Address,
+        from: Address,
+        to: Address,
+        id: U256,
+        value: U256,
+    },
+    /// ERC1155 `TransferBatch` event
+    TransferBatch {
+        operator: Address,
+        from: Address,
+        to: Address,
+        ids: Vec<U256>,
+        values: Vec<U256>,
+    },
+    /// ERC1155 `URI` event
+    Uri {
+        id: U256,
+        value: String,
+    },
+    Generic {
+        name: String,
+        params: Vec<EventParam>,
+    },
+}
+
+/// A parameter of an event decoded with a runtime-loaded ABI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EventParam {
+    /// The name of the parameter as declared in the ABI
+    pub name: String,
+    /// The canonical Solidity type of the parameter, e.g. `uint256` or `(address,bool)[]`
+    pub ty: String,
+    /// The decoded value. Integers are represented as decimal strings, bytes and addresses as hex strings.
+    pub value: serde_json::Value,
+}
+
+/// Metadata of a token contract, read from the contract itself.
+/// Each field is `None` if the contract does not implement the corresponding optional method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub token_address: Address,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    /// The number of decimals used to represent the token amounts
+    pub decimals: Option<u8>,
+}
+
+/// Message sent by the `SubscriberService` to its output channel
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Event {
-    Approval { from: Address, to: Address, value: U256 },
-    Transfer { from: Address, to: Address, value: U256 },
-    Deposit { to: Address, value: U256 },
-    Withdrawal { from: Address, value: U256 },
+#[allow(clippy::large_enum_variant)]
+pub enum SubscriberMessage {
+    /// A decoded event emitted by the token contract
+    Event(Event),
+    /// All the events of the token up to and including the given block have been sent
+    BlockProcessed { token_address: Address, block_number: u64, block_hash: B256 },
+    /// The blocks from `block_number` onward are no longer part of the canonical chain and their events
+    /// must be discarded. `parent_hash` is the hash of the last valid block.
+    ChainReorganized { token_address: Address, block_number: u64, parent_hash: B256 },
+}
+
+/// The finality required before an event is forwarded by the `SubscriberService`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Finality {
+    /// Events are forwarded once their block is deep enough below the latest block
+    #[default]
+    Latest,
+    /// Events are forwarded once their block is covered by the `safe` block tag
+    Safe,
+    /// Events are forwarded once their block is covered by the `finalized` block tag
+    Finalized,
+}
+
+/// The standard implemented by a token contract. It selects the decoder of the contract logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStandard {
+    /// A standard ERC20 token emitting `Transfer` and `Approval` events
+    #[default]
+    Erc20,
+    /// A wrapped ether token emitting the ERC20 events together with `Deposit` and `Withdrawal` events
+    Weth,
+    /// A non-fungible token emitting `Transfer`, `Approval` and `ApprovalForAll` events
+    Erc721,
+    /// A multi token emitting `TransferSingle`, `TransferBatch`, `ApprovalForAll` and `URI` events
+    Erc1155,
+}
+
+/// The transport used by the `SubscriberService` to receive the logs from the Ethereum node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// Logs are pushed by the node through a WebSocket `eth_subscribe` subscription
+    #[default]
+    Ws,
+    /// Logs are polled from the node with `eth_blockNumber` and `eth_getLogs` calls over HTTP
+    Http,
+}
+
+/// The state of the connection of a subscription to the Ethereum node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    /// The first connection is being established
+    #[default]
+    Connecting,
+    /// The subscription is receiving the logs from the node
+    Connected,
+    /// The connection was lost and a new one is being established
+    Reconnecting,
+    /// The subscription was stopped
+    Stopped,
+    /// The subscription terminated because no endpoint could be reached
+    Failed,
+}
+
+/// A snapshot of the status of a subscription
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubscriberStatus {
+    /// The address of the token contract
+    pub token_address: Address,
+    /// The label of the token, or its address
+    pub name: String,
+    pub state: ConnectionState,
+    /// The redacted URL of the endpoint currently used
+    pub endpoint: String,
+    /// The number of the last block seen, either as a new head or as the block of a log
+    pub last_block: Option<u64>,
+    /// The time of the last received event, in seconds since the Unix epoch
+    pub last_event_time: Option<u64>,
+    /// The number of reconnections since the subscription started
+    pub reconnects: u64,
+    /// The number of logs that could not be decoded
+    pub decode_errors: u64,
+}
+
+/// A cloneable handle to the status of a subscription, updated by the subscription task
+pub type StatusHandle = watch::Receiver<SubscriberStatus>;
+
+/// Messages waiting for the confirmation of their block, in chain order
+pub type UnconfirmedMessages = Arc<Mutex<VecDeque<(u64, SubscriberMessage)>>>;
+
+/// A cloneable handle to the events of a subscription that are waiting for the confirmation of their block
+#[derive(Debug, Clone)]
+pub struct UnconfirmedHandle {
+    token_address: Address,
+    pending: UnconfirmedMessages,
+}
+
+impl UnconfirmedHandle {
+    /// Creates a new handle to the messages of the given token buffered in `pending`
+    pub fn new(token_address: Address, pending: UnconfirmedMessages) -> Self {
+        Self { token_address, pending }
+    }
+
+    /// Returns the address of the token contract
+    pub fn token_address(&self) -> Address {
+        self.token_address
+    }
+
+    /// Returns the events waiting for the confirmation of their block, in chain order
+    pub fn events(&self) -> Vec<Event> {
+        self.pending
+            .lock()
+            .expect("The unconfirmed messages lock is poisoned")
+            .iter()
+            .filter_map(|(_, message)| match message {
+                SubscriberMessage::Event(event) => Some(event.clone()),
+                _ => None,
+            })
+            .collect()
+    }
 }
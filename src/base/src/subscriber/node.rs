@@ -0,0 +1,150 @@
+use alloy::{
+    primitives::B256,
+    providers::{DynProvider, Provider, ProviderBuilder, WsConnect},
+    rpc::types::{BlockNumberOrTag, Filter, Log},
+};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::error::CoreError;
+
+use super::model::Transport;
+
+/// An item received from the live subscriptions of a token
+#[allow(clippy::large_enum_variant)]
+pub enum LiveItem {
+    /// A log of the token contract
+    Log(Log),
+    /// The number of a new block header, used to verify that the connection is alive
+    Head(u64),
+    /// The given number of logs were dropped because the buffer of the subscription was full
+    Lagged(u64),
+    /// The node has no more logs to deliver, as at the end of a replayed recording
+    Completed,
+}
+
+/// The merged stream of the live logs and block headers
+pub type LiveStream = BoxStream<'static, LiveItem>;
+
+/// The number of logs buffered by a live subscription while the last blocks are being backfilled
+/// or the receiver of the messages is not keeping up
+const SUBSCRIPTION_CHANNEL_SIZE: usize = 10_000;
+
+/// The requests sent by a subscription to an endpoint of the Ethereum node.
+/// Their failures are reported as [`CoreError::NodeError`], so that they can be told apart from the errors
+/// of the processing of the logs.
+///
+/// It is implemented by [`AlloyNode`] for the real endpoints and by the replay of a recording, so that the live
+/// and the recorded logs are processed by the same subscription task.
+pub trait Node: Send + Sync {
+    /// Returns the number of the latest block
+    fn block_number(&self) -> impl Future<Output = Result<u64, CoreError>> + Send;
+
+    /// Returns the number of the block with the given tag
+    fn tagged_block_number(&self, tag: BlockNumberOrTag) -> impl Future<Output = Result<u64, CoreError>> + Send;
+
+    /// Returns the hash of the canonical block with the given number
+    fn block_hash(&self, block_number: u64) -> impl Future<Output = Result<B256, CoreError>> + Send;
+
+    /// Returns the timestamp of the block with the given hash
+    fn block_timestamp(&self, block_hash: B256) -> impl Future<Output = Result<u64, CoreError>> + Send;
+
+    /// Returns the canonical logs matching the filter
+    fn logs(&self, filter: &Filter) -> impl Future<Output = Result<Vec<Log>, CoreError>> + Send;
+
+    /// Subscribes to the new block headers and, if a filter is provided, to the new logs matching it.
+    /// The returned stream merges both subscriptions; the logs dropped by a full subscription buffer are
+    /// reported with [`LiveItem::Lagged`].
+    fn subscribe(&self, filter: Option<&Filter>) -> impl Future<Output = Result<LiveStream, CoreError>> + Send;
+}
+
+/// Opens the connections to the endpoints of the Ethereum node
+pub trait Connector: Send + Sync + 'static {
+    type Node: Node + 'static;
+
+    /// Connects to the endpoint with the given URL
+    fn connect(&self, url: &str) -> impl Future<Output = anyhow::Result<Self::Node>> + Send;
+}
+
+/// Connects to the endpoints of the Ethereum node with the configured transport
+pub struct AlloyConnector {
+    transport: Transport,
+}
+
+impl AlloyConnector {
+    /// Creates a new `AlloyConnector` for the given transport
+    pub fn new(transport: Transport) -> Self {
+        Self { transport }
+    }
+}
+
+impl Connector for AlloyConnector {
+    type Node = AlloyNode;
+
+    async fn connect(&self, url: &str) -> anyhow::Result<AlloyNode> {
+        let provider = match self.transport {
+            Transport::Ws => ProviderBuilder::new().connect_ws(WsConnect::new(url)).await?.erased(),
+            Transport::Http => ProviderBuilder::new().connect_http(url.parse()?).erased(),
+        };
+        Ok(AlloyNode { provider })
+    }
+}
+
+/// An endpoint of the Ethereum node reached through an alloy provider
+pub struct AlloyNode {
+    provider: DynProvider,
+}
+
+impl Node for AlloyNode {
+    async fn block_number(&self) -> Result<u64, CoreError> {
+        Ok(self.provider.get_block_number().await?)
+    }
+
+    async fn tagged_block_number(&self, tag: BlockNumberOrTag) -> Result<u64, CoreError> {
+        let block = self
+            .provider
+            .get_block_by_number(tag)
+            .await?
+            .ok_or_else(|| CoreError::NodeError(format!("Block {tag} not found")))?;
+        Ok(block.header.number)
+    }
+
+    async fn block_hash(&self, block_number: u64) -> Result<B256, CoreError> {
+        let block = self
+            .provider
+            .get_block_by_number(block_number.into())
+            .await?
+            .ok_or_else(|| CoreError::NodeError(format!("Block {block_number} not found")))?;
+        Ok(block.header.hash)
+    }
+
+    async fn block_timestamp(&self, block_hash: B256) -> Result<u64, CoreError> {
+        let block = self
+            .provider
+            .get_block_by_hash(block_hash)
+            .await?
+            .ok_or_else(|| CoreError::NodeError(format!("Block {block_hash} not found")))?;
+        Ok(block.header.timestamp)
+    }
+
+    async fn logs(&self, filter: &Filter) -> Result<Vec<Log>, CoreError> {
+        Ok(self.provider.get_logs(filter).await?)
+    }
+
+    async fn subscribe(&self, filter: Option<&Filter>) -> Result<LiveStream, CoreError> {
+        let heads = self.provider.subscribe_blocks().await?.into_stream().map(|header| LiveItem::Head(header.number));
+        let Some(filter) = filter else {
+            return Ok(heads.boxed());
+        };
+        let logs = self.provider.subscribe_logs(filter).channel_size(SUBSCRIPTION_CHANNEL_SIZE).await?;
+        // The streams of the subscription silently skip the dropped logs, so they are received one by one
+        let logs = stream::unfold(logs, |mut logs| async move {
+            match logs.recv().await {
+                Ok(log) => Some((LiveItem::Log(log), logs)),
+                Err(RecvError::Lagged(count)) => Some((LiveItem::Lagged(count), logs)),
+                Err(RecvError::Closed) => None,
+            }
+        });
+        Ok(stream::select(logs, heads).boxed())
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, LineWriter, Write},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    primitives::B256,
+    rpc::types::{BlockNumberOrTag, Filter, Log},
+};
+use futures_util::stream::{self, StreamExt};
+use log::*;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+
+use crate::error::CoreError;
+
+use super::node::{Connector, LiveItem, LiveStream, Node};
+
+/// A raw log received from the Ethereum node, as stored in a recording
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RecordedLog {
+    /// The time at which the log was received, in milliseconds since the Unix epoch
+    pub received_at: u64,
+    pub log: Log,
+}
+
+/// Appends the raw logs received by the subscriptions to a JSONL file, one [`RecordedLog`] per line.
+/// It can be cloned to share the same file among the subscriptions of all the tokens.
+#[derive(Clone)]
+pub struct LogRecorder {
+    writer: Arc<Mutex<LineWriter<File>>>,
+}
+
+impl LogRecorder {
+    /// Opens the recording file at the given path, creating it if it does not exist.
+    /// The new logs are appended to the existing ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn create(path: &str) -> anyhow::Result<Self> {
+        info!("LogRecorder - Recording the received logs to {path}");
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { writer: Arc::new(Mutex::new(LineWriter::new(file))) })
+    }
+
+    /// Appends a log to the recording, together with the current time
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log cannot be written to the file.
+    pub fn record(&self, log: &Log) -> anyhow::Result<()> {
+        let received_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let line = serde_json::to_string(&RecordedLog { received_at, log: log.clone() })?;
+        let mut writer = self.writer.lock().expect("The recording lock is poisoned");
+        writeln!(writer, "{line}")?;
+        Ok(())
+    }
+}
+
+/// A recording of raw logs read from a JSONL file written by a [`LogRecorder`], to be replayed by
+/// [`SubscriberService::replay`](super::service::SubscriberService::replay).
+#[derive(Clone)]
+pub struct LogReplayer {
+    logs: Arc<Vec<RecordedLog>>,
+    speed: Option<f64>,
+}
+
+impl LogReplayer {
+    /// Reads the recording at the given path.
+    ///
+    /// The logs are replayed with the original delays between them divided by `speed`, e.g. 1 replays them at the
+    /// original speed and 10 ten times faster. If `speed` is not set, the logs are replayed without delays.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or a line is not a valid recorded log.
+    pub fn open(path: &str, speed: Option<f64>) -> anyhow::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut logs = vec![];
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recorded = serde_json::from_str(&line)
+                .map_err(|err| anyhow::anyhow!("Invalid recorded log at line {} of {path}: {err}", index + 1))?;
+            logs.push(recorded);
+        }
+        info!("LogReplayer - Read {} logs from {path}", logs.len());
+        Ok(Self { logs: Arc::new(logs), speed: speed.filter(|speed| *speed > 0.0) })
+    }
+}
+
+/// A node serving the logs of a recording, in place of the endpoints of the Ethereum node.
+///
+/// The subscriptions deliver the recorded logs in the recorded order, with the original delays between them divided
+/// by the replay speed, and announce a new head when a log belongs to a new block. The logs of every contract
+/// make up the chain used to answer the requests: a removed log discards its block and the following ones, which
+/// are replaced by the block of the next recorded logs with the same number, and a log with a new hash for a known
+/// block replaces it. The position in the recording is shared by all the connections, so that a reconnection
+/// resumes the replay where it stopped.
+#[derive(Clone)]
+pub(super) struct ReplayNode {
+    recording: Arc<Vec<RecordedLog>>,
+    speed: Option<f64>,
+    state: Arc<Mutex<ReplayState>>,
+}
+
+/// The progress of a replay and the chain made of the logs replayed so far
+#[derive(Default)]
+struct ReplayState {
+    /// The index of the next recorded log to deliver
+    position: usize,
+    /// The time at which the last delivered log was received
+    received_at: Option<u64>,
+    /// The latest block announced as a new head
+    head: Option<u64>,
+    /// The hashes of the canonical blocks
+    blocks: BTreeMap<u64, B256>,
+    /// The logs of the canonical blocks, by block number
+    logs: BTreeMap<u64, Vec<Log>>,
+}
+
+impl ReplayState {
+    /// Adds a canonical log to the chain, replacing the known block with the same number if its hash differs
+    fn insert(&mut self, log: &Log) {
+        let (Some(block_number), Some(block_hash)) = (log.block_number, log.block_hash) else {
+            return;
+        };
+        if self.blocks.get(&block_number) != Some(&block_hash) {
+            self.blocks.split_off(&block_number);
+            self.logs.split_off(&block_number);
+            self.blocks.insert(block_number, block_hash);
+        }
+        let logs = self.logs.entry(block_number).or_default();
+        if !logs.iter().any(|known| known.log_index == log.log_index) {
+            logs.push(log.clone());
+        }
+    }
+
+    /// Discards the given block and the following ones, then imports the replacement block from the logs
+    /// that follow in the recording
+    fn remove(&mut self, block_number: u64, block_hash: B256, following: &[RecordedLog]) {
+        if self.blocks.get(&block_number) != Some(&block_hash) {
+            return;
+        }
+        self.blocks.split_off(&block_number);
+        self.logs.split_off(&block_number);
+
+        let mut replacement = following
+            .iter()
+            .map(|recorded| &recorded.log)
+            .filter(|log| !log.removed && log.block_number == Some(block_number) && log.block_hash != Some(block_hash))
+            .peekable();
+        let Some(replacement_hash) = replacement.peek().and_then(|log| log.block_hash) else {
+            return;
+        };
+        for log in replacement.filter(|log| log.block_hash == Some(replacement_hash)) {
+            self.insert(log);
+        }
+    }
+}
+
+impl ReplayNode {
+    /// Creates a new `ReplayNode` that replays the logs of the given recording from the beginning
+    pub(super) fn new(replayer: LogReplayer) -> Self {
+        Self { recording: replayer.logs, speed: replayer.speed, state: Default::default() }
+    }
+
+    fn state(&self) -> MutexGuard<'_, ReplayState> {
+        self.state.lock().expect("The replay lock is poisoned")
+    }
+
+    /// Delivers the next recorded log, after its original delay, and returns the resulting live items.
+    /// Returns `None` when all the logs were delivered.
+    async fn deliver_next(&self, filter: Option<&Filter>) -> Option<Vec<LiveItem>> {
+        let (position, delay) = {
+            let state = self.state();
+            let recorded = self.recording.get(state.position)?;
+            let delay = match (self.speed, state.received_at) {
+                (Some(speed), Some(received_at)) => {
+                    Some(Duration::from_millis(recorded.received_at.saturating_sub(received_at)).div_f64(speed))
+                }
+                _ => None,
+            };
+            (state.position, delay)
+        };
+        if let Some(delay) = delay {
+            sleep(delay).await;
+        }
+
+        let mut state = self.state();
+        if state.position != position {
+            // Delivered meanwhile by another connection
+            return Some(vec![]);
+        }
+        let recorded = &self.recording[position];
+        state.position += 1;
+        state.received_at = Some(recorded.received_at);
+
+        let log = &recorded.log;
+        let (Some(block_number), Some(block_hash)) = (log.block_number, log.block_hash) else {
+            warn!("ReplayNode - Skipping a recorded log without block information: {log:?}");
+            return Some(vec![]);
+        };
+
+        let mut items = vec![];
+        if log.removed {
+            state.remove(block_number, block_hash, &self.recording[position + 1..]);
+        } else {
+            state.insert(log);
+            if state.head.is_none_or(|head| block_number > head) {
+                state.head = Some(block_number);
+                items.push(LiveItem::Head(block_number));
+            }
+        }
+        if filter.is_some_and(|filter| filter.matches(&log.inner)) {
+            items.push(LiveItem::Log(log.clone()));
+        }
+        Some(items)
+    }
+}
+
+impl Connector for ReplayNode {
+    type Node = ReplayNode;
+
+    async fn connect(&self, _url: &str) -> anyhow::Result<ReplayNode> {
+        Ok(self.clone())
+    }
+}
+
+impl Node for ReplayNode {
+    async fn block_number(&self) -> Result<u64, CoreError> {
+        // Before the first log, the chain ends at the block preceding the recording
+        let first_block = self.recording.first().and_then(|recorded| recorded.log.block_number).unwrap_or_default();
+        Ok(self.state().blocks.last_key_value().map_or(first_block.saturating_sub(1), |(number, _)| *number))
+    }
+
+    async fn tagged_block_number(&self, _tag: BlockNumberOrTag) -> Result<u64, CoreError> {
+        self.block_number().await
+    }
+
+    async fn block_hash(&self, block_number: u64) -> Result<B256, CoreError> {
+        self.state()
+            .blocks
+            .get(&block_number)
+            .copied()
+            .ok_or_else(|| CoreError::NodeError(format!("Block {block_number} is not part of the recording")))
+    }
+
+    async fn block_timestamp(&self, block_hash: B256) -> Result<u64, CoreError> {
+        self.state()
+            .logs
+            .values()
+            .flatten()
+            .find(|log| log.block_hash == Some(block_hash))
+            .and_then(|log| log.block_timestamp)
+            .ok_or_else(|| {
+                CoreError::NodeError(format!("The timestamp of block {block_hash} is not part of the recording"))
+            })
+    }
+
+    async fn logs(&self, filter: &Filter) -> Result<Vec<Log>, CoreError> {
+        let state = self.state();
+        let mut logs: Vec<_> = state
+            .logs
+            .iter()
+            .filter(|(block_number, _)| filter.matches_block_range(**block_number))
+            .flat_map(|(_, logs)| logs)
+            .filter(|log| filter.matches(&log.inner))
+            .cloned()
+            .collect();
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+        Ok(logs)
+    }
+
+    async fn subscribe(&self, filter: Option<&Filter>) -> Result<LiveStream, CoreError> {
+        let stream = stream::unfold(Some((self.clone(), filter.cloned())), |state| async move {
+            let (node, filter) = state?;
+            match node.deliver_next(filter.as_ref()).await {
+                Some(items) => Some((items, Some((node, filter)))),
+                None => Some((vec![LiveItem::Completed], None)),
+            }
+        });
+        Ok(stream.flat_map(stream::iter).boxed())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_report_the_line_of_an_invalid_recorded_log() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!("recording-{}.jsonl", rand::random::<u64>()));
+        let path = path.to_string_lossy().to_string();
+        let recorder = LogRecorder::create(&path).unwrap();
+        recorder.record(&Log::default()).unwrap();
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"\n{\"invalid\": true}\n").unwrap();
+
+        // Act
+        let result = LogReplayer::open(&path, None);
+
+        // Assert
+        let err = result.err().expect("The recording should be invalid");
+        assert!(err.to_string().contains("line 3"), "{err}");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,167 +1,1772 @@
-use std::sync::{Arc, atomic::AtomicBool};
+use std::{
+    collections::HashSet,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use alloy::{
-    primitives::Address,
-    providers::{Provider, ProviderBuilder, WsConnect},
+    primitives::{Address, B256},
+    providers::{Provider, ProviderBuilder},
     rpc::types::{BlockNumberOrTag, Filter, Log},
-    sol,
-    sol_types::SolEvent,
 };
-use futures_util::{Stream, stream::StreamExt};
+use futures_util::stream::{self, StreamExt};
 use log::*;
-use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle, time::timeout};
+use tokio::{
+    sync::{mpsc::Sender, watch},
+    task::JoinHandle,
+    time::{Instant, interval, interval_at, sleep, timeout},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    backoff::Backoff,
+    config::{EthNode, EventFilter, Token},
+    endpoint::{ActiveEndpoint, EndpointPool, redact},
+    error::CoreError,
+};
+
+use super::{
+    confirmation::ConfirmationBuffer,
+    decoder::{IWETH9, LogDecoder, participant_topics},
+    model::{
+        ConnectionState, Event, Finality, StatusHandle, SubscriberMessage, SubscriberStatus, TokenMetadata,
+        TokenStandard, Transport, UnconfirmedHandle, UnconfirmedMessages,
+    },
+    node::{AlloyConnector, Connector, LiveItem, LiveStream, Node},
+    replay::{LogRecorder, LogReplayer, ReplayNode},
+    tracker::BlockTracker,
+};
 
-use super::model::Event;
+/// The maximum depth of a chain reorganization that can be detected
+const MAX_REORG_DEPTH: u64 = 128;
 
-// Codegen from ABI file to interact with the contract.
-sol!(
-    #[allow(missing_docs)]
-    #[sol(rpc)]
-    IWETH9,
-    "resources/abi/IWETH9.json"
-);
+/// The interval between two checks of the confirmed block when events are buffered until their confirmation
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(12);
 
-/// Service for subscribing to Ethereum events
+/// Service for subscribing to the Ethereum events of a token
 pub struct SubscriberService {
-    rpc_url: String,
+    wss_url: String,
+    http_url: Option<String>,
+    fallback_urls: Vec<String>,
+    max_endpoint_failures: u32,
+    failback_interval: Duration,
+    active_endpoint: ActiveEndpoint,
+    reconnect_initial_delay: Duration,
+    reconnect_max_delay: Duration,
+    reconnect_jitter: f64,
+    reconnect_max_attempts: Option<u32>,
+    transport: Transport,
+    poll_interval: Duration,
     timeout_seconds: u64,
     token_address: Address,
+    name: String,
+    standard: TokenStandard,
+    abi_path: Option<String>,
+    start_block: Option<u64>,
+    filters: Vec<EventFilter>,
+    backfill_chunk_size: u64,
+    finality: Finality,
+    confirmations: u64,
+    checkpoint: Option<(u64, B256)>,
+    unconfirmed: UnconfirmedMessages,
+    recorder: Option<LogRecorder>,
 }
 
 impl SubscriberService {
-    /// Creates a new instance of `SubscriberService`.
+    /// Creates a new instance of `SubscriberService` for the given token.
+    ///
+    /// `eth_node` provides the connection to the Ethereum node and the settings shared by all the tokens,
+    /// while `token` provides the address of the token contract and its optional start block and label.
+    pub fn new(eth_node: &EthNode, token: &Token) -> Self {
+        let name = token.label.clone().unwrap_or_else(|| token.address.to_string());
+        info!("SubscriberService - New instance created for token {name}");
+        Self {
+            wss_url: eth_node.wss_url.clone(),
+            http_url: eth_node.http_url.clone(),
+            fallback_urls: eth_node.fallback_urls.clone(),
+            max_endpoint_failures: eth_node.max_endpoint_failures,
+            failback_interval: Duration::from_secs(eth_node.failback_interval_seconds.max(1)),
+            active_endpoint: Default::default(),
+            reconnect_initial_delay: Duration::from_millis(eth_node.reconnect_initial_delay_millis),
+            reconnect_max_delay: Duration::from_millis(eth_node.reconnect_max_delay_millis),
+            reconnect_jitter: eth_node.reconnect_jitter,
+            reconnect_max_attempts: eth_node.reconnect_max_attempts,
+            transport: eth_node.transport,
+            poll_interval: Duration::from_millis(eth_node.poll_interval_millis.max(1)),
+            timeout_seconds: eth_node.timeout_seconds,
+            token_address: token.address,
+            name,
+            standard: token.standard,
+            abi_path: token.abi_path.clone(),
+            start_block: token.start_block,
+            filters: token.filters.clone(),
+            backfill_chunk_size: eth_node.backfill_chunk_size.max(1),
+            finality: eth_node.finality,
+            confirmations: eth_node.confirmations,
+            checkpoint: None,
+            unconfirmed: Default::default(),
+            recorder: None,
+        }
+    }
+
+    /// Returns the address of the token contract
+    pub fn token_address(&self) -> Address {
+        self.token_address
+    }
+
+    /// Resumes the subscription from the block following the given checkpoint, overriding the `start_block`.
     ///
-    /// `rpc_url` should be the URL of the Ethereum node WebSocket endpoint to connect to
-    /// `timeout_seconds` is the time in seconds to wait for an event before attempting a reconnection
-    /// `token_address` is the address of the ERC20 token to subscribe to events of.
-    pub fn new(rpc_url: String, timeout_seconds: u64, token_address: Address) -> Self {
-        info!("SubscriberService - New instance created");
-        Self { rpc_url, timeout_seconds, token_address }
+    /// When the subscription starts, the checkpoint block hash is compared with the canonical one; if the block
+    /// was reorganized while the service was not running, the last `MAX_REORG_DEPTH` blocks are rolled back
+    /// and fetched again.
+    pub fn resume_from(&mut self, block_number: u64, block_hash: B256) {
+        info!("SubscriberService - Resuming token {} from block {block_number}", self.name);
+        self.start_block = Some(block_number + 1);
+        self.checkpoint = Some((block_number, block_hash));
+    }
+
+    /// Records every raw log received from the node, including the removed ones, with the given recorder.
+    /// The block timestamp is added to the logs that do not carry it, so that the recording can be replayed
+    /// offline with [`SubscriberService::replay`].
+    pub fn record_to(&mut self, recorder: LogRecorder) {
+        info!("SubscriberService - Recording the logs of token {}", self.name);
+        self.recorder = Some(recorder);
+    }
+
+    /// Returns the redacted URL of the endpoint currently used by the subscription
+    pub fn active_endpoint(&self) -> String {
+        self.active_endpoint.lock().expect("The active endpoint lock is poisoned").clone()
     }
 
     /// Subscribes to Ethereum events for the specific token address and sends them to the provided channel.
     ///
-    /// This function spawns a new tokio task that listens for events then returns a `JoinHandle` for the spawned task.
+    /// This function spawns a new tokio task that listens for events then returns a [`StatusHandle`] to inspect
+    /// the state of the subscription at runtime and an [`UnconfirmedHandle`] to inspect the events waiting for
+    /// their confirmation, together with the `JoinHandle` for the spawned task.
+    ///
+    /// With the `Http` transport, the live subscription is replaced by polling the node for new blocks at the
+    /// configured interval; the new logs are fetched with the same paged `eth_getLogs` calls of the backfill.
+    ///
+    /// The primary endpoint is followed by the configured fallback endpoints. When the connection is lost, or the
+    /// active endpoint fails `max_endpoint_failures` consecutive times, the task rotates to the next endpoint;
+    /// while a fallback endpoint is active, the primary one is periodically probed to fail back to it.
+    ///
+    /// When no endpoint is reachable, the connection is attempted again after an exponential backoff with jitter.
+    /// The task terminates with an error only if the optional maximum number of attempts is reached; in that
+    /// case the error is returned by the `JoinHandle`. It terminates successfully as soon as `shutdown` is
+    /// cancelled, even while waiting for new logs or for a reconnection attempt.
+    ///
+    /// If a `start_block` was provided, the task first pages through the historical logs from that block
+    /// up to the current head and then hands over to the live subscription. Live logs already covered by the
+    /// backfill are discarded, so no event is lost or sent twice at the seam.
+    ///
+    /// The task tracks the hashes of the recently seen blocks. When a log is removed from the canonical chain
+    /// or a known block changes its hash, a [`SubscriberMessage::ChainReorganized`] message is sent and the logs
+    /// of the replacement blocks are fetched again.
+    ///
+    /// When a finality mode other than `Latest` or a number of confirmations is configured, the messages are
+    /// buffered until their block is confirmed. The buffered events can be inspected with the returned
+    /// [`UnconfirmedHandle`].
     ///
     /// # Arguments
     ///
-    /// * `sender` - An unbounded sender to transmit decoded Ethereum events.
-    /// * `run_until` - An atomic boolean that controls the lifetime of the subscription. The task will exit when it is set to `false`.
+    /// * `sender` - A bounded sender to transmit decoded Ethereum events. When the channel is full, the subscription
+    ///   waits for the receiver to catch up, while up to 10 000 new logs are buffered by the
+    ///   subscription. If more logs arrive meanwhile, the oldest ones are dropped: the task then reconnects and
+    ///   fetches again the logs following the last processed block, skipping those already sent.
+    /// * `shutdown` - A token that controls the lifetime of the subscription. The task exits when it is cancelled,
+    ///   dropping its sender.
     ///
     /// # Errors
     ///
-    /// Returns an error if the subscription to the Ethereum node fails.
+    /// Returns an error if the ABI file of the token cannot be loaded or the subscription to the Ethereum node fails.
     pub async fn subscribe_to(
         &self,
-        sender: UnboundedSender<Event>,
-        run_until: Arc<AtomicBool>,
-    ) -> anyhow::Result<JoinHandle<()>> {
-        info!("SubscriberService - Subscribing to events for token {}", self.token_address);
-
-        let filter = Filter::new().address(self.token_address).from_block(BlockNumberOrTag::Latest);
-
-        let rpc_url = self.rpc_url.clone();
-        let timeout_seconds = std::time::Duration::from_secs(self.timeout_seconds);
-
-        let handle = tokio::spawn(async move {
-            let (mut _provider, mut stream) = new_subscription(&rpc_url, &filter, &run_until).await.unwrap();
-
-            loop {
-                let result = timeout(timeout_seconds, stream.next()).await;
-                match result {
-                    Ok(Some(log)) => match decode_log(log, &sender) {
-                        Ok(()) => debug!("Log processed successfully"),
-                        Err(err) => error!("Error while processing received log: {err:?}"),
-                    },
-                    Ok(None) => {
-                        warn!("WS connection was closed. Reconnecting...");
-                        match new_subscription(&rpc_url, &filter, &run_until).await {
-                            Ok((new_provider, new_stream)) => {
-                                _provider = new_provider;
-                                stream = new_stream;
+        sender: Sender<SubscriberMessage>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<(StatusHandle, UnconfirmedHandle, JoinHandle<anyhow::Result<()>>)> {
+        info!("SubscriberService - Subscribing to events for token {} ({})", self.name, self.token_address);
+
+        let primary_url = self.primary_url()?;
+        let urls = std::iter::once(primary_url).chain(self.fallback_urls.iter().cloned()).collect();
+        let (mut task, status_handle) = self.new_task(
+            AlloyConnector::new(self.transport),
+            urls,
+            ConfirmationBuffer::new(self.finality, self.confirmations, self.unconfirmed.clone()),
+            sender,
+            shutdown,
+        )?;
+        task.transport = self.transport;
+        task.checkpoint = self.checkpoint;
+        task.recorder = self.recorder.clone();
+
+        let handle = tokio::spawn(task.run(self.start_block));
+
+        Ok((status_handle, UnconfirmedHandle::new(self.token_address, self.unconfirmed.clone()), handle))
+    }
+
+    /// Replays the recorded logs of the token, instead of subscribing to the Ethereum node, and sends
+    /// the decoded events to the provided channel.
+    ///
+    /// This function spawns a new tokio task that replays the logs, then returns a [`StatusHandle`] to inspect
+    /// the state of the replay and an [`UnconfirmedHandle`], always empty, together with the `JoinHandle` for
+    /// the spawned task.
+    ///
+    /// The recording takes the place of the node: the logs are processed by the same task of the live
+    /// subscription, without any connection to the node, so that the same recording always produces the same
+    /// messages. The requests of the task, e.g. the hash of a block or the logs of a reorganized block, are answered
+    /// from the logs replayed so far. The `start_block`, the checkpoint and the finality settings are not applied,
+    /// as the recorded logs were received before any confirmation. The task terminates successfully when all
+    /// the logs have been replayed or `shutdown` is cancelled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ABI file of the token cannot be loaded. The task returns an error if the `sender`
+    /// channel is closed.
+    pub async fn replay(
+        &self,
+        replayer: LogReplayer,
+        sender: Sender<SubscriberMessage>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<(StatusHandle, UnconfirmedHandle, JoinHandle<anyhow::Result<()>>)> {
+        info!("SubscriberService - Replaying the recorded events of token {} ({})", self.name, self.token_address);
+
+        let (task, status_handle) = self.new_task(
+            ReplayNode::new(replayer),
+            vec!["replay".to_owned()],
+            ConfirmationBuffer::new(Finality::Latest, 0, self.unconfirmed.clone()),
+            sender,
+            shutdown,
+        )?;
+
+        let handle = tokio::spawn(task.run(None));
+
+        Ok((status_handle, UnconfirmedHandle::new(self.token_address, self.unconfirmed.clone()), handle))
+    }
+
+    /// Fetches the name, symbol and decimals of the token from its contract, with the calls defined by
+    /// the WETH ABI. The methods not implemented by the contract, whose calls revert or return no data,
+    /// are reported as `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the primary endpoint of the node is not reachable or if a call fails for any other
+    /// reason than a revert, so that the metadata persisted by a previous run is not overwritten.
+    pub async fn fetch_metadata(&self) -> anyhow::Result<TokenMetadata> {
+        let provider = ProviderBuilder::new().connect(&self.primary_url()?).await?;
+        self.fetch_metadata_from(provider).await
+    }
+
+    /// Fetches the name, symbol and decimals of the token with the given provider
+    async fn fetch_metadata_from<P: Provider>(&self, provider: P) -> anyhow::Result<TokenMetadata> {
+        provider.get_block_number().await?;
+
+        let contract = IWETH9::new(self.token_address, provider);
+        Ok(TokenMetadata {
+            token_address: self.token_address,
+            name: self.implemented("name", contract.name().call().await)?,
+            symbol: self.implemented("symbol", contract.symbol().call().await)?,
+            decimals: self.implemented("decimals", contract.decimals().call().await)?,
+        })
+    }
+
+    /// Returns the result of a metadata call, or `None` if the contract does not implement the method
+    fn implemented<T>(&self, method: &str, result: Result<T, alloy::contract::Error>) -> anyhow::Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if is_revert(&err) => {
+                debug!("SubscriberService - The {method} of token {} is not available: {err:?}", self.name);
+                Ok(None)
+            }
+            Err(err) => Err(anyhow::Error::new(err).context(format!("Failed to fetch the {method} of the token"))),
+        }
+    }
+
+    /// Creates the task of a subscription to the given endpoints, together with the handle to its status.
+    /// The task uses the `Ws` transport, without checkpoint nor recorder.
+    fn new_task<C: Connector>(
+        &self,
+        connector: C,
+        urls: Vec<String>,
+        confirmation: ConfirmationBuffer,
+        sender: Sender<SubscriberMessage>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<(SubscriptionTask<C>, StatusHandle)> {
+        let (status, status_handle) = watch::channel(SubscriberStatus {
+            token_address: self.token_address,
+            name: self.name.clone(),
+            state: ConnectionState::Connecting,
+            endpoint: redact(&urls[0]),
+            last_block: None,
+            last_event_time: None,
+            reconnects: 0,
+            decode_errors: 0,
+        });
+
+        let task = SubscriptionTask {
+            connector,
+            endpoints: EndpointPool::new(urls, self.max_endpoint_failures, self.active_endpoint.clone()),
+            failback_interval: self.failback_interval,
+            backoff: Backoff::new(
+                self.reconnect_initial_delay,
+                self.reconnect_max_delay,
+                self.reconnect_jitter,
+                self.reconnect_max_attempts,
+            ),
+            transport: Transport::Ws,
+            poll_interval: self.poll_interval,
+            token_address: self.token_address,
+            name: self.name.clone(),
+            decoder: LogDecoder::new(self.standard, self.abi_path.as_deref())?,
+            filters: self.node_filters()?,
+            timeout: Duration::from_secs(self.timeout_seconds),
+            backfill_chunk_size: self.backfill_chunk_size,
+            sender,
+            shutdown,
+            status,
+            checkpoint: None,
+            confirmation,
+            tracker: BlockTracker::new(MAX_REORG_DEPTH as usize),
+            backfilled_to: None,
+            current_block: None,
+            processed_to: None,
+            sent_logs: HashSet::new(),
+            head: None,
+            block_timestamp: None,
+            recorder: None,
+        };
+        Ok((task, status_handle))
+    }
+
+    /// Returns the URL of the primary endpoint for the configured transport
+    fn primary_url(&self) -> anyhow::Result<String> {
+        match self.transport {
+            Transport::Ws => Ok(self.wss_url.clone()),
+            Transport::Http => {
+                self.http_url.clone().ok_or_else(|| anyhow::anyhow!("The http transport requires the http_url setting"))
+            }
+        }
+    }
+
+    /// Builds the filters sent to the node: a filter for each configured event filter, with the signature
+    /// of the event and the addresses of its participants as topics, or a single filter on the token address
+    /// if no event filter is configured.
+    fn node_filters(&self) -> anyhow::Result<Vec<Filter>> {
+        let token_filter = Filter::new().address(self.token_address).from_block(BlockNumberOrTag::Latest);
+        if self.filters.is_empty() {
+            return Ok(vec![token_filter]);
+        }
+
+        self.filters
+            .iter()
+            .map(|event_filter| {
+                let (signature, from_index, to_index) = participant_topics(self.standard, &event_filter.event)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("The event {} cannot be filtered for token {}", event_filter.event, self.name)
+                    })?;
+                let mut filter = token_filter.clone().event_signature(signature);
+                for (index, addresses, participant) in
+                    [(from_index, &event_filter.from, "from"), (to_index, &event_filter.to, "to")]
+                {
+                    if addresses.is_empty() {
+                        continue;
+                    }
+                    let index = index.ok_or_else(|| {
+                        anyhow::anyhow!("The event {} has no {participant} participant", event_filter.event)
+                    })?;
+                    filter.topics[index] =
+                        addresses.iter().map(|address| address.into_word()).collect::<Vec<_>>().into();
+                }
+                Ok(filter)
+            })
+            .collect()
+    }
+}
+
+/// The state of the task spawned by [`SubscriberService::subscribe_to`] and [`SubscriberService::replay`]
+struct SubscriptionTask<C: Connector> {
+    /// The connector to the endpoints of the node
+    connector: C,
+    /// The endpoints of the Ethereum node, with the active one
+    endpoints: EndpointPool,
+    failback_interval: Duration,
+    /// The policy of the reconnection attempts when no endpoint is reachable
+    backoff: Backoff,
+    transport: Transport,
+    poll_interval: Duration,
+    token_address: Address,
+    /// The label of the token, or its address, used in the logs
+    name: String,
+    /// The decoder of the token logs
+    decoder: LogDecoder,
+    /// The filters of the logs requested to the node. With more than one filter, the live logs are fetched
+    /// with `eth_getLogs` calls at each new block, as a subscription supports a single filter.
+    filters: Vec<Filter>,
+    /// The time to wait for a new block before reconnecting, also used as the timeout of the probes
+    timeout: Duration,
+    backfill_chunk_size: u64,
+    sender: Sender<SubscriberMessage>,
+    /// The token cancelled when the subscription must stop
+    shutdown: CancellationToken,
+    /// The sender of the status published to the [`StatusHandle`]
+    status: watch::Sender<SubscriberStatus>,
+    /// The checkpoint to be verified when the first subscription is established
+    checkpoint: Option<(u64, B256)>,
+    /// The messages waiting for the confirmation of their block
+    confirmation: ConfirmationBuffer,
+    /// The hashes of the recently seen blocks
+    tracker: BlockTracker,
+    /// The last block sent by the backfill. Live logs up to this block were already sent.
+    backfilled_to: Option<u64>,
+    /// The number and hash of the block of the last received live log
+    current_block: Option<(u64, B256)>,
+    /// The last block whose events were all sent. A new subscription resumes from the following block.
+    processed_to: Option<u64>,
+    /// The transaction hash and log index of the logs sent from the blocks following `processed_to`
+    sent_logs: HashSet<(B256, u64)>,
+    /// The number of the latest block announced by the node
+    head: Option<u64>,
+    /// The hash and timestamp of the last block whose timestamp was fetched from the node
+    block_timestamp: Option<(B256, u64)>,
+    /// The optional recorder of the raw logs received from the node
+    recorder: Option<LogRecorder>,
+}
+
+impl<C: Connector> SubscriptionTask<C> {
+    /// Runs the subscription loop, reconnecting to the node when the connection is lost.
+    /// It returns an error if the maximum number of reconnection attempts is reached.
+    async fn run(mut self, start_block: Option<u64>) -> anyhow::Result<()> {
+        let Some((mut node, mut stream)) = self.reconnect(start_block).await? else {
+            return Ok(());
+        };
+
+        let idle = sleep(self.timeout);
+        tokio::pin!(idle);
+        let mut confirmation_interval = interval(CONFIRMATION_POLL_INTERVAL);
+        let mut poll_interval = interval(self.poll_interval);
+        let mut failback_interval = interval_at(Instant::now() + self.failback_interval, self.failback_interval);
+        let shutdown = self.shutdown.clone();
+
+        loop {
+            tokio::select! {
+                // The items queued while a message was waiting for room in the channel are handled before
+                // the timers, so that a slow receiver is not mistaken for a stalled node
+                biased;
+                _ = shutdown.cancelled() => {
+                    info!("[{}] The subscription was stopped", self.name);
+                    self.status.send_modify(|status| status.state = ConnectionState::Stopped);
+                    return Ok(());
+                }
+                item = stream.next() => match item {
+                    Some(LiveItem::Head(block_number)) => {
+                        if !self.observe_head(block_number) {
+                            continue;
+                        }
+                        idle.as_mut().reset(Instant::now() + self.timeout);
+                        if !self.is_polling() {
+                            continue;
+                        }
+                        match self.poll(&node).await {
+                            Ok(_) => {
+                                self.endpoints.record_success();
+                                continue;
+                            }
+                            Err(err) => {
+                                if !self.record_error("polling the node", err)? {
+                                    continue;
+                                }
+                                warn!("[{}] Too many errors from the endpoint. Rotating...", self.name);
+                                self.endpoints.rotate();
+                            }
+                        }
+                    }
+                    Some(LiveItem::Log(log)) => {
+                        match self.process_live_log(&node, log).await {
+                            Ok(()) => {
+                                debug!("Log processed successfully");
+                                self.endpoints.record_success();
+                                continue;
                             }
                             Err(err) => {
-                                error!("Failed to reconnect. The service will exit. Error: {err:?}");
-                                break;
+                                if !self.record_error("processing a received log", err)? {
+                                    continue;
+                                }
+                                warn!("[{}] Too many errors from the endpoint. Rotating...", self.name);
+                                self.endpoints.rotate();
                             }
                         }
                     }
-                    Err(_err) => {
+                    Some(LiveItem::Lagged(count)) => {
                         warn!(
-                            "WS connection not received any event in {} seconds. Reconnecting...",
-                            timeout_seconds.as_secs()
+                            "[{}] The subscription dropped {count} logs because the messages were not received in time. Reconnecting...",
+                            self.name
                         );
-                        match new_subscription(&rpc_url, &filter, &run_until).await {
-                            Ok((new_provider, new_stream)) => {
-                                _provider = new_provider;
-                                stream = new_stream;
+                    }
+                    Some(LiveItem::Completed) => {
+                        if let Some((block_number, block_hash)) = self.current_block.take() {
+                            self.send_block_processed(block_number, block_hash).await;
+                        }
+                        info!("[{}] The node has no more logs to deliver", self.name);
+                        self.status.send_modify(|status| status.state = ConnectionState::Stopped);
+                        return Ok(());
+                    }
+                    None => {
+                        warn!("[{}] WS connection was closed. Reconnecting...", self.name);
+                        self.endpoints.rotate();
+                    }
+                },
+                _ = poll_interval.tick(), if self.transport == Transport::Http => {
+                    match self.poll(&node).await {
+                        Ok(head) => {
+                            if self.observe_head(head) {
+                                idle.as_mut().reset(Instant::now() + self.timeout);
                             }
-                            Err(err) => {
-                                error!("Failed to reconnect. The service will exit. Error: {err:?}");
-                                break;
+                            self.endpoints.record_success();
+                            continue;
+                        }
+                        Err(err) => {
+                            if !self.record_error("polling the node", err)? {
+                                continue;
                             }
+                            warn!("[{}] Too many errors from the endpoint. Rotating...", self.name);
+                            self.endpoints.rotate();
                         }
                     }
                 }
+                _ = &mut idle => {
+                    warn!(
+                        "[{}] No new block received in {} seconds. Reconnecting...",
+                        self.name,
+                        self.timeout.as_secs()
+                    );
+                    self.endpoints.rotate();
+                }
+                _ = confirmation_interval.tick(), if self.confirmation.is_enabled() => {
+                    if let Err(err) = self.release_confirmed(&node).await {
+                        // The confirmed events are released again at the next tick
+                        self.record_error("releasing the confirmed events", err)?;
+                    }
+                    continue;
+                }
+                _ = failback_interval.tick(), if !self.endpoints.is_primary_active() => {
+                    if !self.probe_primary().await {
+                        continue;
+                    }
+                    info!("[{}] The primary endpoint is available again. Failing back...", self.name);
+                    self.endpoints.fail_back();
+                }
             }
+
+            self.status.send_modify(|status| {
+                status.state = ConnectionState::Reconnecting;
+                status.reconnects += 1;
+            });
+            match self.reconnect(None).await? {
+                Some((new_node, new_stream)) => {
+                    node = new_node;
+                    stream = new_stream;
+                    idle.as_mut().reset(Instant::now() + self.timeout);
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Logs an error raised while processing the logs of the active endpoint and returns true if the endpoint
+    /// must be rotated.
+    ///
+    /// Only the errors of the node count as failures of the endpoint, as a log that cannot be decoded would fail
+    /// with any endpoint. A closed channel terminates the task with an error, since no message can be sent anymore.
+    fn record_error(&mut self, action: &str, err: CoreError) -> anyhow::Result<bool> {
+        match err {
+            CoreError::ChannelClosed => {
+                error!("[{}] The message channel was closed while {action}. The service will exit", self.name);
+                self.status.send_modify(|status| status.state = ConnectionState::Failed);
+                Err(anyhow::Error::new(err).context(format!("The subscription of token {} failed", self.name)))
+            }
+            CoreError::NodeError(_) => {
+                error!("[{}] Error while {action}: {err}", self.name);
+                Ok(self.endpoints.record_failure())
+            }
+            _ => {
+                error!("[{}] Error while {action}: {err}", self.name);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Records the number of the latest block announced by the node.
+    /// Returns true if the chain head advanced since the last observed one.
+    fn observe_head(&mut self, block_number: u64) -> bool {
+        if self.head.is_some_and(|head| block_number <= head) {
+            return false;
+        }
+        trace!("[{}] New head block {block_number}", self.name);
+        self.head = Some(block_number);
+        self.observe_block(block_number);
+        true
+    }
+
+    /// Publishes the given block as the last one seen, if it is newer than the current one
+    fn observe_block(&self, block_number: u64) {
+        self.status.send_if_modified(|status| {
+            if status.last_block.is_some_and(|last_block| block_number <= last_block) {
+                return false;
+            }
+            status.last_block = Some(block_number);
+            true
         });
+    }
 
-        Ok(handle)
+    /// Establishes a new subscription, retrying with an exponential backoff until it succeeds.
+    ///
+    /// Returns `None` if the task was stopped with the `shutdown` token, or an error if the maximum number
+    /// of attempts is reached.
+    async fn reconnect(&mut self, from_block: Option<u64>) -> anyhow::Result<Option<(C::Node, LiveStream)>> {
+        let shutdown = self.shutdown.clone();
+        loop {
+            let result = tokio::select! {
+                result = self.connect(from_block) => result,
+                _ = shutdown.cancelled() => {
+                    info!("[{}] The subscription was stopped", self.name);
+                    self.status.send_modify(|status| status.state = ConnectionState::Stopped);
+                    return Ok(None);
+                }
+            };
+
+            match result {
+                Ok(subscription) => {
+                    self.backoff.reset();
+                    return Ok(Some(subscription));
+                }
+                Err(err) => match self.backoff.next_delay() {
+                    Some(delay) => {
+                        warn!(
+                            "[{}] Failed to connect to any endpoint, attempt {}. Retrying in {} ms. Error: {err:?}",
+                            self.name,
+                            self.backoff.attempts(),
+                            delay.as_millis()
+                        );
+                        tokio::select! {
+                            _ = sleep(delay) => {}
+                            _ = shutdown.cancelled() => {}
+                        }
+                    }
+                    None => {
+                        error!("[{}] Failed to reconnect. The service will exit. Error: {err:?}", self.name);
+                        self.status.send_modify(|status| status.state = ConnectionState::Failed);
+                        return Err(err.context(format!(
+                            "The subscription of token {} failed after {} reconnection attempts",
+                            self.name,
+                            self.backoff.attempts()
+                        )));
+                    }
+                },
+            }
+        }
+    }
+
+    /// Establishes a new subscription, trying each endpoint once starting from the active one
+    async fn connect(&mut self, from_block: Option<u64>) -> anyhow::Result<(C::Node, LiveStream)> {
+        let mut attempts = 0;
+        loop {
+            match self.new_subscription(from_block).await {
+                Ok(subscription) => {
+                    let endpoint = redact(self.endpoints.active_url());
+                    info!("[{}] Connected to endpoint {endpoint}", self.name);
+                    self.status.send_modify(|status| {
+                        status.state = ConnectionState::Connected;
+                        status.endpoint = endpoint;
+                    });
+                    self.endpoints.record_success();
+                    return Ok(subscription);
+                }
+                Err(err) => {
+                    attempts += 1;
+                    warn!(
+                        "[{}] Failed to connect to endpoint {}. Error: {err:?}",
+                        self.name,
+                        redact(self.endpoints.active_url())
+                    );
+                    if attempts >= self.endpoints.len() {
+                        return Err(err);
+                    }
+                    self.endpoints.rotate();
+                }
+            }
+        }
     }
-}
 
-/// Establishes a new subscription to the Ethereum node for the given `rpc_url`.
-async fn new_subscription(
-    rpc_url: &str,
-    filter: &Filter,
-    run_until: &AtomicBool,
-) -> anyhow::Result<(impl Provider, impl Stream<Item = Log>)> {
-    let ws = WsConnect::new(rpc_url);
-    let provider = ProviderBuilder::new().connect_ws(ws).await?;
+    /// Returns true if the primary endpoint answers to a request within the timeout
+    async fn probe_primary(&self) -> bool {
+        let url = self.endpoints.primary_url();
+        let probe = async {
+            self.connector.connect(url).await?.block_number().await?;
+            anyhow::Ok(())
+        };
+        match timeout(self.timeout, probe).await {
+            Ok(Ok(())) => true,
+            Ok(Err(err)) => {
+                debug!("[{}] The primary endpoint is still unavailable: {err:?}", self.name);
+                false
+            }
+            Err(_) => {
+                debug!("[{}] The primary endpoint did not answer in time", self.name);
+                false
+            }
+        }
+    }
 
-    let sub = provider.subscribe_logs(filter).await?;
+    /// Establishes a new subscription to the Ethereum node.
+    ///
+    /// If `from_block` is provided, all the logs from that block up to the current head are fetched and sent
+    /// before the subscription is returned. The live logs up to the last backfilled block will be discarded
+    /// as they were already sent.
+    /// If `from_block` is not provided, the subscription resumes after the last processed block, or from the block
+    /// being processed if none was completed yet, so that the logs emitted while the connection was down or dropped
+    /// by the subscription are not lost. The logs already sent are not sent again.
+    ///
+    /// With the `Http` transport, the returned stream never yields: the new logs are fetched by [`Self::poll`].
+    async fn new_subscription(&mut self, mut from_block: Option<u64>) -> anyhow::Result<(C::Node, LiveStream)> {
+        from_block = from_block
+            .or(self.processed_to.map(|processed_to| processed_to + 1))
+            .or(self.current_block.map(|(block_number, _)| block_number));
 
-    let stream =
-        sub.into_stream().take_while(|_x| async { run_until.load(std::sync::atomic::Ordering::Relaxed) }).boxed();
+        // A block received before the reconnection cannot be considered completely processed
+        self.current_block = None;
+        self.backfilled_to = None;
 
-    Ok((provider, stream))
-}
+        let node = self.connector.connect(self.endpoints.active_url()).await?;
+
+        if let Some((block_number, block_hash)) = self.checkpoint {
+            from_block = Some(self.verify_checkpoint(&node, block_number, block_hash).await?);
+            self.checkpoint = None;
+        }
+
+        // The bulk of the history is fetched before subscribing, so the subscription buffer
+        // only needs to hold the logs emitted while the last few blocks are backfilled.
+        if let Some(from_block) = from_block {
+            let head = node.block_number().await?;
+            self.backfill(&node, from_block, head).await?;
+            self.backfilled_to = Some(head.max(from_block.saturating_sub(1)));
+        }
+
+        if self.is_polling() {
+            // Polling starts from the current head if there is nothing to backfill
+            if self.backfilled_to.is_none() {
+                self.backfilled_to = Some(node.block_number().await?);
+            }
+            if self.transport == Transport::Http {
+                return Ok((node, stream::pending().boxed()));
+            }
+            // The new blocks trigger the polling
+            let heads = node.subscribe(None).await?;
+            return Ok((node, heads));
+        }
+
+        let stream = node.subscribe(Some(&self.filters[0])).await?;
+
+        // Fetch the blocks mined between the end of the backfill and the subscription.
+        if let Some(last_block) = self.backfilled_to {
+            let head = node.block_number().await?;
+            self.backfill(&node, last_block + 1, head).await?;
+            self.backfilled_to = Some(head.max(last_block));
+        }
+
+        Ok((node, stream))
+    }
+
+    /// Returns true if the new logs are fetched by [`Self::poll`] instead of a live subscription
+    fn is_polling(&self) -> bool {
+        self.transport == Transport::Http || self.filters.len() > 1
+    }
+
+    /// Polls the node for the blocks mined since the last poll and sends their logs to the channel.
+    ///
+    /// Before fetching the new logs, the most recent tracked blocks are compared with the canonical ones; if they
+    /// differ, the chain reorganization is handled starting from the block following the last still canonical one.
+    /// Returns the number of the current head block.
+    async fn poll(&mut self, node: &C::Node) -> Result<u64, CoreError> {
+        let mut reorganized_from = None;
+        for (block_number, block_hash) in self.tracker.iter_rev().collect::<Vec<_>>() {
+            if node.block_hash(block_number).await? == block_hash {
+                // The fork may have happened at an untracked block following the last canonical one
+                reorganized_from = reorganized_from.map(|_| block_number + 1);
+                break;
+            }
+            reorganized_from = Some(block_number);
+        }
+
+        if let Some(block_number) = reorganized_from {
+            self.handle_reorg(node, block_number).await?;
+            return node.block_number().await;
+        }
+
+        let head = node.block_number().await?;
+        let from_block = self.backfilled_to.map_or(head, |backfilled_to| backfilled_to + 1);
+        self.backfill(node, from_block, head).await?;
+        self.backfilled_to = Some(head.max(from_block.saturating_sub(1)));
+        Ok(head)
+    }
+
+    /// Verifies that the checkpoint block is still part of the canonical chain and returns the block
+    /// from which the subscription should start.
+    /// If the checkpoint block was reorganized, the last `MAX_REORG_DEPTH` blocks are rolled back.
+    async fn verify_checkpoint(&mut self, node: &C::Node, block_number: u64, block_hash: B256) -> anyhow::Result<u64> {
+        if node.block_hash(block_number).await? == block_hash {
+            self.tracker.insert(block_number, block_hash);
+            return Ok(block_number + 1);
+        }
+
+        // The genesis block has no parent to roll back to
+        let rollback_from = block_number.saturating_sub(MAX_REORG_DEPTH - 1).max(1);
+        warn!(
+            "[{}] Checkpoint block {block_number} is no longer canonical. Rolling back to block {rollback_from}",
+            self.name
+        );
+        Ok(self.send_chain_reorganized(node, rollback_from).await?)
+    }
+
+    /// Fetches the logs from `from_block` to `to_block` (both inclusive) with `eth_getLogs` calls spanning
+    /// at most `backfill_chunk_size` blocks each, and sends the decoded events to the channel in chain order.
+    /// The completion of each chunk is notified with a [`SubscriberMessage::BlockProcessed`] message.
+    async fn backfill(&mut self, node: &C::Node, from_block: u64, to_block: u64) -> Result<(), CoreError> {
+        if from_block > to_block {
+            return Ok(());
+        }
+        info!("[{}] Backfilling logs from block {from_block} to block {to_block}", self.name);
+
+        let mut chunk_start = from_block;
+        while chunk_start <= to_block && !self.shutdown.is_cancelled() {
+            let chunk_end = chunk_start.saturating_add(self.backfill_chunk_size - 1).min(to_block);
+            let mut logs = vec![];
+            for filter in &self.filters {
+                logs.extend(node.logs(&filter.clone().from_block(chunk_start).to_block(chunk_end)).await?);
+            }
+            // The logs matching more than one filter are sent only once
+            logs.sort_by_key(|log| (log.block_number, log.log_index));
+            logs.dedup_by_key(|log| (log.block_number, log.log_index));
+            debug!("Backfill - Fetched {} logs from block {chunk_start} to block {chunk_end}", logs.len());
+
+            for log in logs {
+                self.record(node, &log).await;
+                match self.send_log(node, log).await {
+                    Ok(()) => debug!("Log processed successfully"),
+                    Err(CoreError::DecodeError(err)) => error!("Error while decoding backfilled log: {err}"),
+                    Err(err) => return Err(err),
+                }
+            }
+
+            let block_hash = node.block_hash(chunk_end).await?;
+            self.tracker.insert(chunk_end, block_hash);
+            self.send_block_processed(chunk_end, block_hash).await;
+
+            if self.confirmation.is_enabled() {
+                self.release_confirmed(node).await?;
+            }
+
+            chunk_start = chunk_end + 1;
+        }
+        Ok(())
+    }
+
+    /// Processes a log received from the live subscription.
+    ///
+    /// Logs already sent by the backfill are discarded. When the log belongs to a new block, the previous block
+    /// is notified as processed. Logs removed from the canonical chain, or belonging to a tracked block whose hash
+    /// has changed, trigger the handling of a chain reorganization.
+    async fn process_live_log(&mut self, node: &C::Node, log: Log) -> Result<(), CoreError> {
+        self.record(node, &log).await;
+
+        let (Some(block_number), Some(block_hash)) = (log.block_number, log.block_hash) else {
+            return Err(CoreError::DecodeError(format!("Received a log without block information: {log:?}")));
+        };
+
+        if log.removed {
+            // Only the removal of a log of the tracked canonical chain is relevant;
+            // the others belong to blocks that were already rolled back.
+            if self.tracker.get(block_number) == Some(block_hash) {
+                return self.handle_reorg(node, block_number).await;
+            }
+            trace!("Ignoring removed log of block {block_number} that was already rolled back");
+            return Ok(());
+        }
+
+        if self.tracker.is_reorganized(block_number, block_hash) {
+            return self.handle_reorg(node, block_number).await;
+        }
 
-/// Decodes and sends an Ethereum event to the provided channel.
-fn decode_log(log: Log, sender: &UnboundedSender<Event>) -> anyhow::Result<()> {
-    match log.topic0() {
-        // Match the `Approval(address,address,uint256)` event.
-        Some(&IWETH9::Approval::SIGNATURE_HASH) => {
-            let IWETH9::Approval { src, guy, wad } = log.log_decode()?.inner.data;
-            debug!("Received event from subscription: Approval from {src} to {guy} of value {wad}");
-            sender.send(Event::Approval { from: src, to: guy, value: wad })?;
+        if self.backfilled_to.is_some_and(|backfilled_to| block_number <= backfilled_to) {
+            trace!("Skipping live log of block {block_number} already sent by the backfill");
+            return Ok(());
         }
 
-        // Match the `Transfer(address,address,uint256)` event.
-        Some(&IWETH9::Transfer::SIGNATURE_HASH) => {
-            let IWETH9::Transfer { src, dst, wad } = log.log_decode()?.inner.data;
-            debug!("Received event from subscription: Transfer from {src} to {dst} of value {wad}");
-            sender.send(Event::Transfer { from: src, to: dst, value: wad })?;
+        // A log delivered again, e.g. by a recording that contains it twice
+        if self.processed_to.is_some_and(|processed_to| block_number <= processed_to) {
+            trace!("Skipping live log of block {block_number} already processed");
+            return Ok(());
+        }
+
+        match self.current_block {
+            Some((current_number, current_hash)) if block_number > current_number => {
+                self.send_block_processed(current_number, current_hash).await;
+                self.current_block = Some((block_number, block_hash));
+            }
+            None => self.current_block = Some((block_number, block_hash)),
+            _ => {}
         }
 
-        // Match the `Deposit(address,uint256)` event.
-        Some(&IWETH9::Deposit::SIGNATURE_HASH) => {
-            let IWETH9::Deposit { dst, wad } = log.log_decode()?.inner.data;
-            debug!("Received event from subscription: Deposit to {dst} of value {wad}");
-            sender.send(Event::Deposit { to: dst, value: wad })?;
+        self.send_log(node, log).await
+    }
+
+    /// Handles a chain reorganization starting at the given block.
+    /// The reorganization is notified to the channel, then the logs from that block up to the current head
+    /// are fetched again from the new canonical chain.
+    async fn handle_reorg(&mut self, node: &C::Node, block_number: u64) -> Result<(), CoreError> {
+        warn!("[{}] Chain reorganization detected at block {block_number}", self.name);
+        self.current_block = None;
+        let block_number = self.send_chain_reorganized(node, block_number).await?;
+
+        let head = node.block_number().await?;
+        self.backfill(node, block_number, head).await?;
+        self.backfilled_to = Some(head.max(block_number.saturating_sub(1)));
+        Ok(())
+    }
+
+    /// Forgets the blocks from `block_number` onward and notifies the channel that their events must be discarded.
+    ///
+    /// The notification carries the hash of the parent block, which becomes the checkpoint of the token. If the
+    /// node cannot return it, e.g. because the parent block is not part of a replayed recording, the
+    /// reorganization starts after the last tracked block preceding `block_number` instead: no log of the token
+    /// was seen in between. Returns the first reorganized block.
+    async fn send_chain_reorganized(&mut self, node: &C::Node, block_number: u64) -> Result<u64, CoreError> {
+        let (block_number, parent_hash) = self.find_parent(node, block_number).await?;
+        self.tracker.rollback(block_number);
+        self.processed_to = self.processed_to.map(|processed_to| processed_to.min(block_number.saturating_sub(1)));
+        self.sent_logs.clear();
+
+        // Only the reorganizations of already forwarded blocks need to be notified
+        if !self.confirmation.rollback(block_number) {
+            debug!("Discarded the unconfirmed messages from block {block_number} because of a chain reorganization");
+            return Ok(block_number);
+        }
+
+        self.sender
+            .send(SubscriberMessage::ChainReorganized { token_address: self.token_address, block_number, parent_hash })
+            .await?;
+        Ok(block_number)
+    }
+
+    /// Returns the first block of a chain reorganization starting at `block_number`, together with the hash of its
+    /// parent block, as described in [`Self::send_chain_reorganized`]
+    async fn find_parent(&self, node: &C::Node, block_number: u64) -> Result<(u64, B256), CoreError> {
+        let parent = block_number
+            .checked_sub(1)
+            .ok_or_else(|| CoreError::NodeError("The genesis block has no parent".to_owned()))?;
+        match node.block_hash(parent).await {
+            Ok(parent_hash) => Ok((block_number, parent_hash)),
+            Err(err) => {
+                let (tracked_number, tracked_hash) =
+                    self.tracker.iter_rev().find(|(tracked_number, _)| *tracked_number < block_number).ok_or_else(
+                        || CoreError::NodeError(format!("The parent of block {block_number} is unknown: {err}")),
+                    )?;
+                warn!(
+                    "[{}] The parent of block {block_number} is unknown. The reorganization starts after the tracked block {tracked_number}",
+                    self.name
+                );
+                Ok((tracked_number + 1, tracked_hash))
+            }
+        }
+    }
+
+    /// Forwards the buffered messages whose block has been confirmed
+    async fn release_confirmed(&mut self, node: &C::Node) -> Result<(), CoreError> {
+        let confirmed_block = match self.confirmation.finality() {
+            Finality::Latest => self.confirmation.confirmed_block_at(node.block_number().await?),
+            Finality::Safe => node.tagged_block_number(BlockNumberOrTag::Safe).await?,
+            Finality::Finalized => node.tagged_block_number(BlockNumberOrTag::Finalized).await?,
+        };
+
+        let messages = self.confirmation.release(confirmed_block);
+        if !messages.is_empty() {
+            debug!("Forwarding {} messages confirmed up to block {confirmed_block}", messages.len());
+        }
+        for message in messages {
+            self.sender.send(message).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends a message of the given block to the channel, or buffers it until the block is confirmed.
+    /// If the channel is full, it waits until the receiver makes room for the message.
+    async fn send(&mut self, block_number: u64, message: SubscriberMessage) -> Result<(), CoreError> {
+        if let Some(message) = self.confirmation.push(block_number, message) {
+            self.sender.send(message).await?;
+        }
+        Ok(())
+    }
+
+    /// Decodes a log and sends the resulting event, together with its position in the chain, to the channel.
+    /// Logs of unknown events are ignored.
+    async fn send_log(&mut self, node: &C::Node, log: Log) -> Result<(), CoreError> {
+        let event_type = match self.decoder.decode(&log) {
+            Ok(Some(event_type)) => event_type,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                self.status.send_modify(|status| status.decode_errors += 1);
+                return Err(CoreError::DecodeError(format!("{err:?}")));
+            }
+        };
+
+        let (Some(block_number), Some(block_hash), Some(transaction_hash), Some(transaction_index), Some(log_index)) =
+            (log.block_number, log.block_hash, log.transaction_hash, log.transaction_index, log.log_index)
+        else {
+            return Err(CoreError::DecodeError(format!(
+                "Received a log without block or transaction information: {log:?}"
+            )));
+        };
+
+        if self.sent_logs.contains(&(transaction_hash, log_index)) {
+            trace!("Skipping log {log_index} of transaction {transaction_hash} already sent");
+            return Ok(());
+        }
+
+        self.tracker.insert(block_number, block_hash);
+
+        let block_timestamp = match log.block_timestamp {
+            Some(block_timestamp) => block_timestamp,
+            None => self.fetch_block_timestamp(node, block_hash).await?,
+        };
+
+        let event = Event {
+            token_address: self.token_address,
+            block_number,
+            block_hash,
+            block_timestamp,
+            transaction_hash,
+            transaction_index,
+            log_index,
+            event_type,
+            transaction: None,
+        };
+        self.send(block_number, SubscriberMessage::Event(event)).await?;
+        self.observe_block(block_number);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        self.status.send_modify(|status| status.last_event_time = Some(now));
+        self.sent_logs.insert((transaction_hash, log_index));
+        Ok(())
+    }
+
+    /// Records a raw log received from the node, if a recorder is configured.
+    /// The missing block timestamp of a canonical log is fetched, so that the recording is self-contained.
+    /// Recording errors are logged without interrupting the subscription.
+    async fn record(&mut self, node: &C::Node, log: &Log) {
+        let Some(recorder) = self.recorder.clone() else {
+            return;
+        };
+
+        let mut log = log.clone();
+        if let (None, Some(block_hash), false) = (log.block_timestamp, log.block_hash, log.removed) {
+            match self.fetch_block_timestamp(node, block_hash).await {
+                Ok(block_timestamp) => log.block_timestamp = Some(block_timestamp),
+                Err(err) => warn!("[{}] Failed to fetch the timestamp of a recorded log: {err:?}", self.name),
+            }
+        }
+        if let Err(err) = recorder.record(&log) {
+            error!("[{}] Failed to record a log: {err:?}", self.name);
+        }
+    }
+
+    /// Returns the timestamp of the given block.
+    /// The timestamp of the last requested block is cached, as consecutive logs usually belong to the same block.
+    async fn fetch_block_timestamp(&mut self, node: &C::Node, block_hash: B256) -> Result<u64, CoreError> {
+        if let Some((cached_hash, timestamp)) = self.block_timestamp {
+            if cached_hash == block_hash {
+                return Ok(timestamp);
+            }
         }
+        let timestamp = node.block_timestamp(block_hash).await?;
+        self.block_timestamp = Some((block_hash, timestamp));
+        Ok(timestamp)
+    }
 
-        // Match the `Withdrawal(address,uint256)` event.
-        Some(&IWETH9::Withdrawal::SIGNATURE_HASH) => {
-            let IWETH9::Withdrawal { src, wad } = log.log_decode()?.inner.data;
-            debug!("Received event from subscription: Withdrawal from {src} of value {wad}");
-            sender.send(Event::Withdrawal { from: src, value: wad })?;
+    /// Notifies that all the events up to and including the given block were sent
+    async fn send_block_processed(&mut self, block_number: u64, block_hash: B256) {
+        trace!("Block {block_number} processed");
+        let message = SubscriberMessage::BlockProcessed { token_address: self.token_address, block_number, block_hash };
+        if let Err(err) = self.send(block_number, message).await {
+            error!("Failed to send block processed message: {err:?}");
         }
+        self.processed_to = Some(block_number);
+        self.sent_logs.clear();
+    }
+}
 
-        // Unknown event
-        event => {
-            warn!("Received unknown event: {event:?}");
+/// Returns true if a contract call failed because the contract reverted or returned no data,
+/// rather than because of the node or the transport
+fn is_revert(err: &alloy::contract::Error) -> bool {
+    match err {
+        alloy::contract::Error::ZeroData(..) | alloy::contract::Error::AbiError(_) => true,
+        alloy::contract::Error::TransportError(err) => {
+            err.as_error_resp().is_some_and(|payload| payload.code == 3 || payload.message.contains("revert"))
         }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    };
+
+    use alloy::{
+        primitives::{Bytes, LogData, U64, U256},
+        sol_types::{SolCall, SolEvent},
+        transports::mock::Asserter,
     };
-    Ok(())
+    use tokio::sync::mpsc::{self, Receiver, UnboundedSender};
+
+    use crate::config::test::get_settings;
+
+    use super::*;
+
+    fn new_service(standard: TokenStandard, filters: Vec<EventFilter>) -> SubscriberService {
+        let settings = get_settings();
+        let token = Token { standard, filters, ..settings.eth_node.tokens[0].clone() };
+        SubscriberService::new(&settings.eth_node, &token)
+    }
+
+    /// A node serving an in-memory chain, whose live subscription is fed by the test
+    #[derive(Clone, Default)]
+    struct MockNode {
+        chain: Arc<Mutex<MockChain>>,
+    }
+
+    #[derive(Default)]
+    struct MockChain {
+        /// The hash and the logs of the canonical blocks
+        blocks: BTreeMap<u64, (B256, Vec<Log>)>,
+        /// The sender of the items of the last live subscription
+        live: Option<UnboundedSender<LiveItem>>,
+        /// The number of connections opened to the node
+        connections: u32,
+        /// The block ranges of the `logs` requests
+        requested_logs: Vec<(u64, u64)>,
+    }
+
+    impl MockNode {
+        fn chain(&self) -> std::sync::MutexGuard<'_, MockChain> {
+            self.chain.lock().unwrap()
+        }
+
+        /// Mines a new canonical block with a deposit log for each given amount, replacing the known block with
+        /// the same number and the following ones. Returns the logs of the block.
+        fn mine(&self, block_number: u64, token_address: Address, amounts: &[u64]) -> Vec<Log> {
+            let block_hash = B256::random();
+            let logs: Vec<_> = amounts
+                .iter()
+                .enumerate()
+                .map(|(index, amount)| {
+                    let data: LogData =
+                        IWETH9::Deposit { dst: Address::random(), wad: U256::from(*amount) }.encode_log_data();
+                    Log {
+                        inner: alloy::primitives::Log { address: token_address, data },
+                        block_hash: Some(block_hash),
+                        block_number: Some(block_number),
+                        block_timestamp: Some(block_number * 12),
+                        transaction_hash: Some(B256::random()),
+                        transaction_index: Some(index as u64),
+                        log_index: Some(index as u64),
+                        removed: false,
+                    }
+                })
+                .collect();
+            let mut chain = self.chain();
+            chain.blocks.split_off(&block_number);
+            chain.blocks.insert(block_number, (block_hash, logs.clone()));
+            logs
+        }
+
+        /// Returns the hash of a canonical block
+        fn hash_of(&self, block_number: u64) -> B256 {
+            self.chain().blocks[&block_number].0
+        }
+
+        /// Sends an item to the live subscription
+        fn push(&self, item: LiveItem) {
+            let chain = self.chain();
+            assert!(
+                chain.live.as_ref().expect("No live subscription").send(item).is_ok(),
+                "The subscription is closed"
+            );
+        }
+
+        /// Closes the live subscription, as a dropped connection
+        fn disconnect(&self) {
+            self.chain().live = None;
+        }
+
+        /// Waits until the given number of connections were opened and the last one subscribed
+        async fn wait_for_subscription(&self, connections: u32) {
+            timeout(Duration::from_secs(5), async {
+                while self.chain().connections < connections
+                    || self.chain().live.as_ref().is_none_or(|live| live.is_closed())
+                {
+                    sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("The subscription was not established");
+        }
+    }
+
+    impl Connector for MockNode {
+        type Node = MockNode;
+
+        async fn connect(&self, _url: &str) -> anyhow::Result<MockNode> {
+            self.chain().connections += 1;
+            Ok(self.clone())
+        }
+    }
+
+    impl Node for MockNode {
+        async fn block_number(&self) -> Result<u64, CoreError> {
+            Ok(self.chain().blocks.last_key_value().map_or(0, |(block_number, _)| *block_number))
+        }
+
+        async fn tagged_block_number(&self, _tag: BlockNumberOrTag) -> Result<u64, CoreError> {
+            self.block_number().await
+        }
+
+        async fn block_hash(&self, block_number: u64) -> Result<B256, CoreError> {
+            let chain = self.chain();
+            let (block_hash, _) =
+                chain.blocks.get(&block_number).ok_or_else(|| CoreError::NodeError("Unknown block".to_owned()))?;
+            Ok(*block_hash)
+        }
+
+        async fn block_timestamp(&self, _block_hash: B256) -> Result<u64, CoreError> {
+            Err(CoreError::NodeError("The mocked logs carry their timestamp".to_owned()))
+        }
+
+        async fn logs(&self, filter: &Filter) -> Result<Vec<Log>, CoreError> {
+            let mut chain = self.chain();
+            let from_block = filter.get_from_block().unwrap_or_default();
+            let to_block = filter.get_to_block().unwrap_or(u64::MAX);
+            chain.requested_logs.push((from_block, to_block));
+            Ok(chain
+                .blocks
+                .range(from_block..=to_block)
+                .flat_map(|(_, (_, logs))| logs)
+                .filter(|log| filter.matches(&log.inner))
+                .cloned()
+                .collect())
+        }
+
+        async fn subscribe(&self, _filter: Option<&Filter>) -> Result<LiveStream, CoreError> {
+            let (tx, rx) = mpsc::unbounded_channel();
+            self.chain().live = Some(tx);
+            Ok(stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|item| (item, rx)) }).boxed())
+        }
+    }
+
+    /// Creates the task of a subscription to the given node, with the settings of the WETH token
+    fn new_task(
+        service: &SubscriberService,
+        node: MockNode,
+    ) -> (SubscriptionTask<MockNode>, StatusHandle, Receiver<SubscriberMessage>, CancellationToken) {
+        let (tx, rx) = mpsc::channel(1000);
+        let shutdown = CancellationToken::new();
+        let (task, status) = service
+            .new_task(
+                node,
+                vec!["mock".to_owned()],
+                ConfirmationBuffer::new(Finality::Latest, 0, Default::default()),
+                tx,
+                shutdown.clone(),
+            )
+            .unwrap();
+        (task, status, rx, shutdown)
+    }
+
+    /// Receives the given number of messages from the channel
+    async fn receive(rx: &mut Receiver<SubscriberMessage>, count: usize) -> Vec<SubscriberMessage> {
+        let mut messages = vec![];
+        for _ in 0..count {
+            messages.push(timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap());
+        }
+        messages
+    }
+
+    /// Returns the block numbers of the `BlockProcessed` messages
+    fn processed_blocks(messages: &[SubscriberMessage]) -> Vec<u64> {
+        messages
+            .iter()
+            .filter_map(|message| match message {
+                SubscriberMessage::BlockProcessed { block_number, .. } => Some(*block_number),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn should_backfill_from_the_start_block_in_chunks() {
+        // Arrange
+        let mut service = new_service(TokenStandard::Weth, vec![]);
+        service.backfill_chunk_size = 3;
+        let node = MockNode::default();
+        for block_number in 10..=16 {
+            node.mine(block_number, service.token_address, &[block_number]);
+        }
+        let (task, status, mut rx, shutdown) = new_task(&service, node.clone());
+
+        // Act
+        let handle = tokio::spawn(task.run(Some(10)));
+        let messages = receive(&mut rx, 10).await;
+        shutdown.cancel();
+        handle.await.unwrap().unwrap();
+
+        // Assert
+        assert_eq!(vec![12, 15, 16], processed_blocks(&messages));
+        let deposits: Vec<_> = messages
+            .iter()
+            .filter_map(|message| match message {
+                SubscriberMessage::Event(event) => Some(event.block_number),
+                _ => None,
+            })
+            .collect();
+        assert_eq!((10..=16).collect::<Vec<_>>(), deposits);
+        assert_eq!(vec![(10, 12), (13, 15), (16, 16)], node.chain().requested_logs);
+        assert_eq!(Some(16), status.borrow().last_block);
+        assert_eq!(ConnectionState::Stopped, status.borrow().state);
+    }
+
+    #[tokio::test]
+    async fn should_roll_back_after_the_last_tracked_block_when_the_parent_is_unknown() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        node.mine(10, service.token_address, &[]);
+        node.mine(12, service.token_address, &[]);
+        let (mut task, _status, mut rx, _shutdown) = new_task(&service, node.clone());
+        task.tracker.insert(10, node.hash_of(10));
+        task.tracker.insert(12, node.hash_of(12));
+
+        // Act
+        let block_number = task.send_chain_reorganized(&node, 12).await.unwrap();
+
+        // Assert
+        assert_eq!(11, block_number);
+        assert_eq!(
+            SubscriberMessage::ChainReorganized {
+                token_address: service.token_address,
+                block_number: 11,
+                parent_hash: node.hash_of(10)
+            },
+            rx.recv().await.unwrap()
+        );
+        assert_eq!(Some(node.hash_of(10)), task.tracker.get(10));
+        assert_eq!(None, task.tracker.get(12));
+    }
+
+    #[tokio::test]
+    async fn should_fail_the_rollback_when_no_parent_is_known() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        node.mine(12, service.token_address, &[]);
+        let (mut task, _status, mut rx, _shutdown) = new_task(&service, node.clone());
+        task.tracker.insert(12, node.hash_of(12));
+
+        // Act
+        let result = task.send_chain_reorganized(&node, 12).await;
+
+        // Assert
+        assert!(result.is_err());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn should_fetch_the_missed_logs_after_a_connection_dropped_in_the_middle_of_a_block() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        let first_block = node.mine(10, service.token_address, &[1]);
+        let partial_block = node.mine(11, service.token_address, &[2, 3, 4]);
+        let (task, status, mut rx, shutdown) = new_task(&service, node.clone());
+        let handle = tokio::spawn(task.run(None));
+        node.wait_for_subscription(1).await;
+
+        // Act
+        node.push(LiveItem::Log(first_block[0].clone()));
+        node.push(LiveItem::Log(partial_block[0].clone()));
+        let sent = receive(&mut rx, 3).await;
+        node.mine(12, service.token_address, &[5]);
+        node.disconnect();
+        node.wait_for_subscription(2).await;
+        let fetched = receive(&mut rx, 4).await;
+        shutdown.cancel();
+        handle.await.unwrap().unwrap();
+
+        // Assert
+        assert_eq!(vec![10], processed_blocks(&sent));
+        assert!(
+            matches!(&sent[2], SubscriberMessage::Event(event) if event.block_number == 11 && event.log_index == 0)
+        );
+        let fetched_logs: Vec<_> = fetched
+            .iter()
+            .filter_map(|message| match message {
+                SubscriberMessage::Event(event) => Some((event.block_number, event.log_index)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![(11, 1), (11, 2), (12, 0)], fetched_logs);
+        assert_eq!(vec![12], processed_blocks(&fetched));
+        assert_eq!(vec![(11, 12)], node.chain().requested_logs);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(1, status.borrow().reconnects);
+    }
+
+    #[test]
+    fn should_observe_only_the_heads_that_advance_the_chain() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let (mut task, status, _rx, _shutdown) = new_task(&service, MockNode::default());
+
+        // Act
+        let observed: Vec<_> =
+            [10, 10, 9, 12].into_iter().map(|block_number| task.observe_head(block_number)).collect();
+
+        // Assert
+        assert_eq!(vec![true, false, false, true], observed);
+        assert_eq!(Some(12), task.head);
+        assert_eq!(Some(12), status.borrow().last_block);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_reconnect_only_when_the_head_stalls() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        node.mine(10, service.token_address, &[]);
+        let (task, status, _rx, shutdown) = new_task(&service, node.clone());
+        let handle = tokio::spawn(task.run(None));
+        node.wait_for_subscription(1).await;
+
+        // Act
+        // A quiet token: new blocks without logs, each within the timeout
+        for block_number in 11..=15 {
+            sleep(Duration::from_secs(service.timeout_seconds) / 2).await;
+            node.mine(block_number, service.token_address, &[]);
+            node.push(LiveItem::Head(block_number));
+        }
+        let quiet_connections = node.chain().connections;
+
+        // A stalled node: no new block within the timeout
+        sleep(Duration::from_secs(service.timeout_seconds + 1)).await;
+        node.wait_for_subscription(2).await;
+        shutdown.cancel();
+        handle.await.unwrap().unwrap();
+
+        // Assert
+        assert_eq!(1, quiet_connections);
+        assert_eq!(2, node.chain().connections);
+        assert_eq!(1, status.borrow().reconnects);
+        assert_eq!(Some(15), status.borrow().last_block);
+    }
+
+    #[tokio::test]
+    async fn should_poll_the_blocks_mined_since_the_last_poll() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        for block_number in 10..=12 {
+            node.mine(block_number, service.token_address, &[block_number]);
+        }
+        let (mut task, _status, mut rx, _shutdown) = new_task(&service, node.clone());
+        task.tracker.insert(10, node.hash_of(10));
+        task.backfilled_to = Some(10);
+
+        // Act
+        let first_head = task.poll(&node).await.unwrap();
+        let second_head = task.poll(&node).await.unwrap();
+
+        // Assert
+        assert_eq!(12, first_head);
+        assert_eq!(12, second_head);
+        assert_eq!(Some(12), task.backfilled_to);
+        assert_eq!(vec![(11, 12)], node.chain().requested_logs);
+        let messages = receive(&mut rx, 3).await;
+        assert!(matches!(&messages[0], SubscriberMessage::Event(event) if event.block_number == 11));
+        assert!(matches!(&messages[1], SubscriberMessage::Event(event) if event.block_number == 12));
+        assert_eq!(vec![12], processed_blocks(&messages));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn should_detect_a_fork_at_an_untracked_block() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        node.mine(10, service.token_address, &[10]);
+        node.mine(11, service.token_address, &[]);
+        node.mine(12, service.token_address, &[]);
+        node.mine(13, service.token_address, &[13]);
+        let (mut task, _status, mut rx, _shutdown) = new_task(&service, node.clone());
+        task.tracker.insert(10, node.hash_of(10));
+        task.tracker.insert(13, node.hash_of(13));
+        task.backfilled_to = Some(13);
+        // The fork replaces the untracked block 12 and the following ones
+        node.mine(12, service.token_address, &[]);
+        node.mine(13, service.token_address, &[130]);
+
+        // Act
+        let head = task.poll(&node).await.unwrap();
+
+        // Assert
+        assert_eq!(13, head);
+        assert_eq!(
+            SubscriberMessage::ChainReorganized {
+                token_address: service.token_address,
+                block_number: 11,
+                parent_hash: node.hash_of(10)
+            },
+            rx.recv().await.unwrap()
+        );
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            SubscriberMessage::Event(event) if event.block_number == 13 && event.block_hash == node.hash_of(13)
+        ));
+        assert_eq!(vec![(11, 13)], node.chain().requested_logs);
+        assert_eq!(Some(node.hash_of(13)), task.tracker.get(13));
+        assert_eq!(Some(13), task.backfilled_to);
+    }
+
+    #[tokio::test]
+    async fn should_return_the_head_after_a_reorganization() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        for block_number in 10..=12 {
+            node.mine(block_number, service.token_address, &[block_number]);
+        }
+        let (mut task, _status, mut rx, _shutdown) = new_task(&service, node.clone());
+        for block_number in 10..=12 {
+            task.tracker.insert(block_number, node.hash_of(block_number));
+        }
+        task.backfilled_to = Some(12);
+        for block_number in 11..=14 {
+            node.mine(block_number, service.token_address, &[block_number * 10]);
+        }
+
+        // Act
+        let head = task.poll(&node).await.unwrap();
+
+        // Assert
+        assert_eq!(14, head);
+        assert_eq!(Some(14), task.backfilled_to);
+        let messages = receive(&mut rx, 6).await;
+        assert_eq!(
+            SubscriberMessage::ChainReorganized {
+                token_address: service.token_address,
+                block_number: 11,
+                parent_hash: node.hash_of(10)
+            },
+            messages[0]
+        );
+        let events: Vec<_> = messages
+            .iter()
+            .filter_map(|message| match message {
+                SubscriberMessage::Event(event) => Some((event.block_number, event.block_hash)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            (11..=14).map(|block_number| (block_number, node.hash_of(block_number))).collect::<Vec<_>>(),
+            events
+        );
+        assert_eq!(Some(node.hash_of(14)), task.tracker.get(14));
+    }
+
+    #[tokio::test]
+    async fn should_fetch_again_the_logs_dropped_by_a_lagging_subscription() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        let logs = node.mine(10, service.token_address, &[1, 2]);
+        node.mine(11, service.token_address, &[3]);
+        let (task, status, mut rx, shutdown) = new_task(&service, node.clone());
+        let handle = tokio::spawn(task.run(None));
+        node.wait_for_subscription(1).await;
+
+        // Act
+        node.push(LiveItem::Log(logs[0].clone()));
+        let sent = receive(&mut rx, 1).await;
+        node.push(LiveItem::Lagged(2));
+        node.wait_for_subscription(2).await;
+        let fetched = receive(&mut rx, 3).await;
+        shutdown.cancel();
+        handle.await.unwrap().unwrap();
+
+        // Assert
+        assert!(matches!(&sent[0], SubscriberMessage::Event(event) if event.log_index == 0));
+        assert!(
+            matches!(&fetched[0], SubscriberMessage::Event(event) if event.block_number == 10 && event.log_index == 1)
+        );
+        assert!(matches!(&fetched[1], SubscriberMessage::Event(event) if event.block_number == 11));
+        assert_eq!(vec![11], processed_blocks(&fetched));
+        assert_eq!(vec![(10, 11)], node.chain().requested_logs);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(1, status.borrow().reconnects);
+    }
+
+    #[tokio::test]
+    async fn should_count_only_the_node_errors_as_failures_of_the_endpoint() {
+        // Arrange
+        let mut service = new_service(TokenStandard::Weth, vec![]);
+        service.max_endpoint_failures = 1;
+        let (mut task, status, _rx, _shutdown) = new_task(&service, MockNode::default());
+
+        // Act
+        let decode_error = task.record_error("processing a received log", CoreError::DecodeError("invalid".to_owned()));
+        let node_error = task.record_error("polling the node", CoreError::NodeError("timeout".to_owned()));
+
+        // Assert
+        assert!(!decode_error.unwrap());
+        assert!(node_error.unwrap());
+        assert_eq!(ConnectionState::Connecting, status.borrow().state);
+    }
+
+    #[tokio::test]
+    async fn should_terminate_when_the_channel_is_closed() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        let log = node.mine(10, service.token_address, &[1]).remove(0);
+        let (mut task, status, rx, _shutdown) = new_task(&service, node.clone());
+        drop(rx);
+
+        // Act
+        let err = task.process_live_log(&node, log).await.unwrap_err();
+        let result = task.record_error("processing a received log", err);
+
+        // Assert
+        assert!(result.is_err());
+        assert_eq!(ConnectionState::Failed, status.borrow().state);
+    }
+
+    #[tokio::test]
+    async fn should_report_an_undecodable_log_as_a_decode_error() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let node = MockNode::default();
+        let mut log = node.mine(10, service.token_address, &[1]).remove(0);
+        log.inner.data = LogData::new_unchecked(log.inner.data.topics().to_vec(), Bytes::from_static(&[1, 2, 3]));
+        let (mut task, status, _rx, _shutdown) = new_task(&service, node.clone());
+
+        // Act
+        let result = task.process_live_log(&node, log).await;
+
+        // Assert
+        assert!(matches!(result, Err(CoreError::DecodeError(_))));
+        assert_eq!(1, status.borrow().decode_errors);
+    }
+
+    #[tokio::test]
+    async fn should_fetch_the_available_metadata() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let asserter = Asserter::new();
+        asserter.push_success(&U64::from(22_000_000));
+        asserter.push_success(&Bytes::from(IWETH9::nameCall::abi_encode_returns(&"Wrapped Ether".to_owned())));
+        asserter.push_failure_msg("execution reverted");
+        asserter.push_success(&Bytes::from(IWETH9::decimalsCall::abi_encode_returns(&18)));
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter);
+
+        // Act
+        let metadata = service.fetch_metadata_from(provider).await.unwrap();
+
+        // Assert
+        assert_eq!(service.token_address, metadata.token_address);
+        assert_eq!(Some("Wrapped Ether".to_owned()), metadata.name);
+        assert_eq!(None, metadata.symbol);
+        assert_eq!(Some(18), metadata.decimals);
+    }
+
+    #[tokio::test]
+    async fn should_fail_the_metadata_when_a_call_is_rejected_by_the_node() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let asserter = Asserter::new();
+        asserter.push_success(&U64::from(22_000_000));
+        asserter.push_success(&Bytes::from(IWETH9::nameCall::abi_encode_returns(&"Wrapped Ether".to_owned())));
+        asserter.push_failure_msg("rate limit exceeded");
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter);
+
+        // Act
+        let result = service.fetch_metadata_from(provider).await;
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_fail_the_metadata_when_the_node_is_unreachable() {
+        // Arrange
+        let service = new_service(TokenStandard::Weth, vec![]);
+        let asserter = Asserter::new();
+        asserter.push_failure_msg("connection refused");
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter);
+
+        // Act
+        let result = service.fetch_metadata_from(provider).await;
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn should_build_a_node_filter_for_each_event_filter() {
+        // Arrange
+        let (from, to) = (Address::random(), Address::random());
+        let service = new_service(
+            TokenStandard::Erc1155,
+            vec![
+                EventFilter { event: "TransferSingle".to_owned(), from: vec![from], to: vec![] },
+                EventFilter { event: "TransferBatch".to_owned(), from: vec![], to: vec![to] },
+            ],
+        );
+
+        // Act
+        let filters = service.node_filters().unwrap();
+
+        // Assert
+        assert_eq!(2, filters.len());
+        assert!(
+            filters[0].topics[0].contains(&participant_topics(TokenStandard::Erc1155, "TransferSingle").unwrap().0)
+        );
+        assert!(filters[0].topics[2].contains(&from.into_word()));
+        assert!(filters[0].topics[3].is_empty());
+        assert!(filters[1].topics[3].contains(&to.into_word()));
+        assert!(filters.iter().all(|filter| filter.address.contains(&service.token_address)));
+    }
+
+    #[test]
+    fn should_fail_if_the_event_cannot_be_filtered() {
+        // Arrange
+        let unknown = new_service(
+            TokenStandard::Erc20,
+            vec![EventFilter { event: "Deposit".to_owned(), from: vec![], to: vec![Address::random()] }],
+        );
+        let no_sender = new_service(
+            TokenStandard::Weth,
+            vec![EventFilter { event: "Deposit".to_owned(), from: vec![Address::random()], to: vec![] }],
+        );
+
+        // Act & Assert
+        assert!(unknown.node_filters().is_err());
+        assert!(no_sender.node_filters().is_err());
+    }
 }
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use alloy::primitives::B256;
+
+/// Keeps track of the hashes of the most recent blocks seen by a subscription.
+/// It is used to detect when a previously seen block is no longer part of the canonical chain.
+pub struct BlockTracker {
+    blocks: BTreeMap<u64, B256>,
+    max_blocks: usize,
+}
+
+impl BlockTracker {
+    /// Creates a new `BlockTracker` that remembers at most `max_blocks` blocks
+    pub fn new(max_blocks: usize) -> Self {
+        Self { blocks: BTreeMap::new(), max_blocks: max_blocks.max(1) }
+    }
+
+    /// Returns the hash of the given block, if it is tracked
+    pub fn get(&self, block_number: u64) -> Option<B256> {
+        self.blocks.get(&block_number).copied()
+    }
+
+    /// Tracks the hash of a block. The oldest blocks are discarded when the maximum size is exceeded.
+    pub fn insert(&mut self, block_number: u64, block_hash: B256) {
+        self.blocks.insert(block_number, block_hash);
+        while self.blocks.len() > self.max_blocks {
+            self.blocks.pop_first();
+        }
+    }
+
+    /// Returns the tracked blocks, from the most recent one
+    pub fn iter_rev(&self) -> impl Iterator<Item = (u64, B256)> + '_ {
+        self.blocks.iter().rev().map(|(block_number, block_hash)| (*block_number, *block_hash))
+    }
+
+    /// Forgets all the blocks from `block_number` onward
+    pub fn rollback(&mut self, block_number: u64) {
+        self.blocks.split_off(&block_number);
+    }
+
+    /// Returns true if the given block is tracked with a different hash,
+    /// which means that the tracked block is no longer part of the canonical chain
+    pub fn is_reorganized(&self, block_number: u64, block_hash: B256) -> bool {
+        self.get(block_number).is_some_and(|tracked_hash| tracked_hash != block_hash)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_keep_only_the_most_recent_blocks() {
+        // Arrange
+        let mut tracker = BlockTracker::new(3);
+
+        // Act
+        for block_number in 0..5 {
+            tracker.insert(block_number, B256::with_last_byte(block_number as u8));
+        }
+
+        // Assert
+        assert_eq!(None, tracker.get(1));
+        assert_eq!(Some(B256::with_last_byte(2)), tracker.get(2));
+        assert_eq!(Some(B256::with_last_byte(4)), tracker.get(4));
+    }
+
+    #[test]
+    fn should_detect_reorganized_blocks() {
+        // Arrange
+        let mut tracker = BlockTracker::new(10);
+        tracker.insert(10, B256::with_last_byte(10));
+
+        // Assert
+        assert!(!tracker.is_reorganized(10, B256::with_last_byte(10)));
+        assert!(tracker.is_reorganized(10, B256::with_last_byte(11)));
+        assert!(!tracker.is_reorganized(11, B256::with_last_byte(11)));
+    }
+
+    #[test]
+    fn should_rollback_blocks() {
+        // Arrange
+        let mut tracker = BlockTracker::new(10);
+        for block_number in 0..5 {
+            tracker.insert(block_number, B256::with_last_byte(block_number as u8));
+        }
+
+        // Act
+        tracker.rollback(3);
+
+        // Assert
+        assert_eq!(Some(B256::with_last_byte(2)), tracker.get(2));
+        assert_eq!(None, tracker.get(3));
+        assert_eq!(None, tracker.get(4));
+    }
+
+    #[test]
+    fn should_iterate_from_the_most_recent_block() {
+        // Arrange
+        let mut tracker = BlockTracker::new(10);
+        for block_number in [5, 2, 8] {
+            tracker.insert(block_number, B256::with_last_byte(block_number as u8));
+        }
+
+        // Act
+        let blocks: Vec<_> = tracker.iter_rev().map(|(block_number, _)| block_number).collect();
+
+        // Assert
+        assert_eq!(vec![8, 5, 2], blocks);
+    }
+}
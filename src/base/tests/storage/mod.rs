@@ -1,20 +1,41 @@
+use std::time::Duration;
+
 use crate::get_settings;
 use c3p0::sqlx::SqlxPgC3p0Pool;
-use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{
+    PgPool,
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
 
 mod service_it;
 
-pub async fn new_pg_pool() -> SqlxPgC3p0Pool {
+/// Returns the options to connect to the test database
+fn connect_options() -> PgConnectOptions {
     let settings = get_settings().database;
-
-    let options = PgConnectOptions::new()
+    PgConnectOptions::new()
         .username(&settings.username)
         .password(&settings.password)
         .database(&settings.database)
         .host(&settings.host)
-        .port(settings.port);
+        .port(settings.port)
+}
 
-    let pool = PgPoolOptions::new().max_connections(settings.max_connections).connect_with(options).await.unwrap();
+pub async fn new_pg_pool() -> SqlxPgC3p0Pool {
+    let settings = get_settings().database;
+
+    let pool =
+        PgPoolOptions::new().max_connections(settings.max_connections).connect_with(connect_options()).await.unwrap();
 
     SqlxPgC3p0Pool::new(pool)
 }
+
+/// Creates a pool with a single connection that fails fast when the connection is not available,
+/// so that the tests can make the database unreachable by holding the connection
+pub async fn new_single_connection_pool() -> PgPool {
+    PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(Duration::from_millis(200))
+        .connect_with(connect_options())
+        .await
+        .unwrap()
+}
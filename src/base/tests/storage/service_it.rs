@@ -1,14 +1,49 @@
-use crate::storage::new_pg_pool;
-use alloy::primitives::{Address, U256};
+use crate::storage::{new_pg_pool, new_single_connection_pool};
+use alloy::primitives::{Address, B256, I256, U256};
 use base::{
     storage::{
-        model::{EthEventData, EthEventType, EthEventTypeDiscriminants},
+        model::{
+            DiscrepancyData, EthEventData, EthEventParam, EthEventType, EthEventTypeDiscriminants, EthTransactionData,
+            PersistedEvent, ReconciliationData, StorageEvent,
+        },
         service::StorageService,
     },
-    subscriber::model::Event,
+    subscriber::model::{Event, EventParam, EventType, SubscriberMessage, TokenMetadata, TransactionInfo},
 };
+use c3p0::sqlx::SqlxPgC3p0Pool;
 use rand::random;
 
+/// Creates an event data of the given type with random value and chain position
+fn random_event_data(event_type: EthEventType) -> EthEventData {
+    EthEventData {
+        token_address: Address::random(),
+        value: U256::from(random::<u64>()),
+        event_type,
+        block_number: random::<u32>() as u64,
+        block_hash: B256::random(),
+        block_timestamp: random::<u32>() as u64,
+        transaction_hash: B256::random(),
+        transaction_index: random::<u8>() as u64,
+        log_index: random::<u16>() as u64,
+        transaction: None,
+    }
+}
+
+/// Creates an event of the given type with a random chain position
+fn random_event(event_type: EventType) -> Event {
+    Event {
+        token_address: Address::random(),
+        block_number: random::<u32>() as u64,
+        block_hash: B256::random(),
+        block_timestamp: random::<u32>() as u64,
+        transaction_hash: B256::random(),
+        transaction_index: random::<u8>() as u64,
+        log_index: random::<u16>() as u64,
+        event_type,
+        transaction: None,
+    }
+}
+
 /// Tests that events can be saved and retrieved from the repository
 #[tokio::test]
 async fn test_eth_event_storage() {
@@ -25,12 +60,14 @@ async fn test_eth_event_storage() {
         for _ in 0..10 {
             approve_events.push(
                 storage
-                    .save_event(EthEventData {
-                        event_type: EthEventType::Approve { from: Address::random(), to: Address::random() },
-                        value: U256::from(random::<u64>()),
-                    })
+                    .save_event(random_event_data(EthEventType::Approve {
+                        from: Address::random(),
+                        to: Address::random(),
+                    }))
                     .await
-                    .unwrap(),
+                    .unwrap()
+                    .model()
+                    .clone(),
             );
         }
 
@@ -38,12 +75,14 @@ async fn test_eth_event_storage() {
         for _ in 0..10 {
             transfer_events.push(
                 storage
-                    .save_event(EthEventData {
-                        event_type: EthEventType::Transfer { from: Address::random(), to: Address::random() },
-                        value: U256::from(random::<u64>()),
-                    })
+                    .save_event(random_event_data(EthEventType::Transfer {
+                        from: Address::random(),
+                        to: Address::random(),
+                    }))
                     .await
-                    .unwrap(),
+                    .unwrap()
+                    .model()
+                    .clone(),
             );
         }
     }
@@ -52,8 +91,10 @@ async fn test_eth_event_storage() {
     {
         let approve_first_id = approve_events[0].id;
 
-        let approve_events_from_storage =
-            storage.fetch_all_events(Some(EthEventTypeDiscriminants::Approve), approve_first_id, 10).await.unwrap();
+        let approve_events_from_storage = storage
+            .fetch_all_events(Some(EthEventTypeDiscriminants::Approve), None, approve_first_id, 10)
+            .await
+            .unwrap();
         assert_eq!(approve_events_from_storage.len(), 10);
         assert_eq!(approve_first_id, approve_events_from_storage[0].id);
 
@@ -67,8 +108,10 @@ async fn test_eth_event_storage() {
     {
         let approve_first_id = approve_events[1].id;
 
-        let approve_events_from_storage =
-            storage.fetch_all_events(Some(EthEventTypeDiscriminants::Approve), approve_first_id, 4).await.unwrap();
+        let approve_events_from_storage = storage
+            .fetch_all_events(Some(EthEventTypeDiscriminants::Approve), None, approve_first_id, 4)
+            .await
+            .unwrap();
 
         assert_eq!(approve_events_from_storage.len(), 4);
         assert_eq!(approve_first_id, approve_events_from_storage[0].id);
@@ -83,8 +126,10 @@ async fn test_eth_event_storage() {
     {
         let transfer_first_id = transfer_events[0].id;
 
-        let transfer_events_from_storage =
-            storage.fetch_all_events(Some(EthEventTypeDiscriminants::Transfer), transfer_first_id, 10).await.unwrap();
+        let transfer_events_from_storage = storage
+            .fetch_all_events(Some(EthEventTypeDiscriminants::Transfer), None, transfer_first_id, 10)
+            .await
+            .unwrap();
 
         assert_eq!(transfer_events_from_storage.len(), 10);
         assert_eq!(transfer_first_id, transfer_events_from_storage[0].id);
@@ -98,8 +143,10 @@ async fn test_eth_event_storage() {
     // Assert - Fetch Transfer Logs with offset and limit
     {
         let transfer_first_id = transfer_events[0].id;
-        let transfer_events_from_storage =
-            storage.fetch_all_events(Some(EthEventTypeDiscriminants::Transfer), transfer_first_id, 3).await.unwrap();
+        let transfer_events_from_storage = storage
+            .fetch_all_events(Some(EthEventTypeDiscriminants::Transfer), None, transfer_first_id, 3)
+            .await
+            .unwrap();
 
         assert_eq!(transfer_events_from_storage.len(), 3);
         assert_eq!(transfer_first_id, transfer_events_from_storage[0].id);
@@ -117,7 +164,7 @@ async fn test_save_events_from_receiver_stream() {
     // Arrange
     let pool = new_pg_pool().await;
     let storage = StorageService::new(pool).await.unwrap();
-    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
 
     let events_count = 50;
     let mut sent_events = vec![];
@@ -129,26 +176,65 @@ async fn test_save_events_from_receiver_stream() {
     // simulate 50 random events
     for _ in 0..events_count {
         {
-            let event =
-                Event::Approval { from: Address::random(), to: Address::random(), value: U256::from(random::<u64>()) };
+            let event = random_event(EventType::Approval {
+                from: Address::random(),
+                to: Address::random(),
+                value: U256::from(random::<u64>()),
+            });
             sent_events.push(event.clone());
-            tx.send(event).unwrap();
+            tx.send(SubscriberMessage::Event(event)).await.unwrap();
+        }
+        {
+            let event = random_event(EventType::Transfer {
+                from: Address::random(),
+                to: Address::random(),
+                value: U256::from(random::<u64>()),
+            });
+            sent_events.push(event.clone());
+            tx.send(SubscriberMessage::Event(event)).await.unwrap();
+        }
+        {
+            let event = random_event(EventType::Deposit { to: Address::random(), value: U256::from(random::<u64>()) });
+            sent_events.push(event.clone());
+            tx.send(SubscriberMessage::Event(event)).await.unwrap();
         }
         {
             let event =
-                Event::Transfer { from: Address::random(), to: Address::random(), value: U256::from(random::<u64>()) };
+                random_event(EventType::Withdrawal { from: Address::random(), value: U256::from(random::<u64>()) });
+            sent_events.push(event.clone());
+            tx.send(SubscriberMessage::Event(event)).await.unwrap();
+        }
+        {
+            let event = random_event(EventType::NftTransfer {
+                from: Address::random(),
+                to: Address::random(),
+                token_id: U256::from(random::<u64>()),
+            });
             sent_events.push(event.clone());
-            tx.send(event).unwrap();
+            tx.send(SubscriberMessage::Event(event)).await.unwrap();
         }
         {
-            let event = Event::Deposit { to: Address::random(), value: U256::from(random::<u64>()) };
+            let event = random_event(EventType::TransferSingle {
+                operator: Address::random(),
+                from: Address::random(),
+                to: Address::random(),
+                id: U256::from(random::<u64>()),
+                value: U256::from(random::<u64>()),
+            });
             sent_events.push(event.clone());
-            tx.send(event).unwrap();
+            tx.send(SubscriberMessage::Event(event)).await.unwrap();
         }
         {
-            let event = Event::Withdrawal { from: Address::random(), value: U256::from(random::<u64>()) };
+            let event = random_event(EventType::Generic {
+                name: "Sync".to_owned(),
+                params: vec![EventParam {
+                    name: "reserve0".to_owned(),
+                    ty: "uint112".to_owned(),
+                    value: random::<u64>().to_string().into(),
+                }],
+            });
             sent_events.push(event.clone());
-            tx.send(event).unwrap();
+            tx.send(SubscriberMessage::Event(event)).await.unwrap();
         }
     }
 
@@ -156,8 +242,9 @@ async fn test_save_events_from_receiver_stream() {
     drop(tx);
 
     // wait for all events to be processed until the channel is closed
-    while let Some(event) = response_rx.recv().await {
-        received_events.push(event);
+    while let Some(StorageEvent::Persisted(event)) = response_rx.recv().await {
+        assert!(event.is_new());
+        received_events.push(event.model().clone());
     }
 
     // Assert
@@ -165,36 +252,777 @@ async fn test_save_events_from_receiver_stream() {
     assert_eq!(sent_events.len(), received_events.len());
 
     for (sent, received) in sent_events.iter().zip(received_events.iter()) {
-        match sent {
-            Event::Approval { from, to, value } => {
+        assert_eq!(sent.block_number, received.data.block_number);
+        assert_eq!(sent.block_hash, received.data.block_hash);
+        assert_eq!(sent.block_timestamp, received.data.block_timestamp);
+        assert_eq!(sent.transaction_hash, received.data.transaction_hash);
+        assert_eq!(sent.transaction_index, received.data.transaction_index);
+        assert_eq!(sent.log_index, received.data.log_index);
+
+        match &sent.event_type {
+            EventType::Approval { from, to, value } => {
                 assert_eq!(value, &received.data.value);
                 assert_eq!(
                     EthEventType::Approve { from: from.to_owned(), to: to.to_owned() },
                     received.data.event_type
                 );
             }
-            Event::Transfer { from, to, value } => {
+            EventType::Transfer { from, to, value } => {
                 assert_eq!(value, &received.data.value);
                 assert_eq!(
                     EthEventType::Transfer { from: from.to_owned(), to: to.to_owned() },
                     received.data.event_type
                 );
             }
-            Event::Deposit { to, value } => {
+            EventType::Deposit { to, value } => {
                 assert_eq!(value, &received.data.value);
                 assert_eq!(EthEventType::Deposit { to: to.to_owned() }, received.data.event_type);
             }
-            Event::Withdrawal { from, value } => {
+            EventType::Withdrawal { from, value } => {
                 assert_eq!(value, &received.data.value);
                 assert_eq!(EthEventType::Withdrawal { from: from.to_owned() }, received.data.event_type);
             }
+            EventType::NftTransfer { from, to, token_id } => {
+                assert_eq!(U256::ZERO, received.data.value);
+                assert_eq!(
+                    EthEventType::NftTransfer { from: *from, to: *to, token_id: *token_id },
+                    received.data.event_type
+                );
+            }
+            EventType::TransferSingle { operator, from, to, id, value } => {
+                assert_eq!(value, &received.data.value);
+                assert_eq!(
+                    EthEventType::TransferSingle { operator: *operator, from: *from, to: *to, id: *id },
+                    received.data.event_type
+                );
+            }
+            EventType::Generic { name, params } => {
+                let params = params
+                    .iter()
+                    .map(|param| EthEventParam {
+                        name: param.name.clone(),
+                        ty: param.ty.clone(),
+                        value: param.value.clone(),
+                    })
+                    .collect();
+                assert_eq!(EthEventType::Generic { name: name.clone(), params }, received.data.event_type);
+            }
+            event_type => panic!("Unexpected event type {event_type:?}"),
         }
     }
 
     // Assert that all events are persisted
 
     for event in received_events.iter() {
-        let fetched_event = storage.fetch_all_events(None, event.id, 1).await.unwrap();
+        let fetched_event = storage.fetch_all_events(None, None, event.id, 1).await.unwrap();
         assert_eq!(event, &fetched_event[0]);
     }
 }
+
+/// Tests that the checkpoint of a token is updated when a block processed message is received
+#[tokio::test]
+async fn test_update_checkpoint_from_receiver_stream() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+    let token_address = Address::random();
+
+    // Act
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+
+    assert!(storage.fetch_checkpoint(&token_address).await.unwrap().is_none());
+
+    let last_block_hash = B256::random();
+    for block_number in 100..110 {
+        let event = random_event(EventType::Deposit { to: Address::random(), value: U256::from(random::<u64>()) });
+        tx.send(SubscriberMessage::Event(event)).await.unwrap();
+        let block_hash = if block_number == 109 { last_block_hash } else { B256::random() };
+        tx.send(SubscriberMessage::BlockProcessed { token_address, block_number, block_hash }).await.unwrap();
+    }
+
+    // checkpoint of another token
+    tx.send(SubscriberMessage::BlockProcessed {
+        token_address: Address::random(),
+        block_number: 1000,
+        block_hash: B256::random(),
+    })
+    .await
+    .unwrap();
+
+    // Drop the sender to close the channel
+    drop(tx);
+
+    // wait for all events to be processed until the channel is closed
+    let mut received_events = 0;
+    while response_rx.recv().await.is_some() {
+        received_events += 1;
+    }
+
+    // Assert
+    assert_eq!(10, received_events);
+
+    let checkpoint = storage.fetch_checkpoint(&token_address).await.unwrap().unwrap();
+    assert_eq!(token_address, checkpoint.data.token_address);
+    assert_eq!(109, checkpoint.data.block_number);
+    assert_eq!(last_block_hash, checkpoint.data.block_hash);
+}
+
+/// Tests that the details of the transaction of an enriched event are persisted
+#[tokio::test]
+async fn test_save_enriched_events() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+
+    let mut event = random_event(EventType::Deposit { to: Address::random(), value: U256::from(random::<u64>()) });
+    let transaction = TransactionInfo {
+        from: Address::random(),
+        to: Some(event.token_address),
+        gas_used: random::<u32>() as u64,
+        effective_gas_price: random::<u64>() as u128,
+        status: true,
+    };
+    event.transaction = Some(transaction.clone());
+
+    // Act
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+    tx.send(SubscriberMessage::Event(event)).await.unwrap();
+    drop(tx);
+
+    // Assert
+    let Some(StorageEvent::Persisted(persisted)) = response_rx.recv().await else {
+        panic!("Expected a persisted event")
+    };
+    let fetched_events = storage.fetch_all_events(None, None, persisted.model().id, 1).await.unwrap();
+    assert_eq!(
+        Some(EthTransactionData {
+            from: transaction.from,
+            to: transaction.to,
+            gas_used: transaction.gas_used,
+            effective_gas_price: transaction.effective_gas_price,
+            status: transaction.status,
+        }),
+        fetched_events[0].data.transaction
+    );
+}
+
+/// Tests that events with the same transaction hash and log index are persisted only once
+#[tokio::test]
+async fn test_save_duplicated_events_only_once() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+
+    let event = random_event(EventType::Deposit { to: Address::random(), value: U256::from(random::<u64>()) });
+
+    // Act
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+
+    tx.send(SubscriberMessage::Event(event.clone())).await.unwrap();
+    tx.send(SubscriberMessage::Event(event.clone())).await.unwrap();
+
+    // Drop the sender to close the channel
+    drop(tx);
+
+    let Some(StorageEvent::Persisted(first)) = response_rx.recv().await else { panic!("Expected a persisted event") };
+    let Some(StorageEvent::Persisted(second)) = response_rx.recv().await else { panic!("Expected a persisted event") };
+
+    let mut event_data = random_event_data(EthEventType::Deposit { to: Address::random() });
+    event_data.transaction_hash = event.transaction_hash;
+    event_data.log_index = event.log_index;
+    let third = storage.save_event(event_data).await.unwrap();
+
+    // Assert
+    assert!(matches!(first, PersistedEvent::New(_)));
+    assert!(matches!(second, PersistedEvent::Duplicate(_)));
+    assert!(matches!(third, PersistedEvent::Duplicate(_)));
+    assert_eq!(first.model(), second.model());
+    assert_eq!(first.model(), third.model());
+    assert!(response_rx.recv().await.is_none());
+
+    let fetched_events = storage.fetch_all_events(None, None, first.model().id, 2).await.unwrap();
+    assert_eq!(first.model(), &fetched_events[0]);
+    assert!(fetched_events.iter().skip(1).all(|fetched| fetched.data.transaction_hash != event.transaction_hash));
+}
+
+/// Tests that a chain reorganization removes the events of the reorganized blocks and rolls back the checkpoint
+#[tokio::test]
+async fn test_rollback_events_on_chain_reorganization() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+    let token_address = Address::random();
+
+    // Use blocks far beyond the ones generated by the other tests
+    let first_block = u32::MAX as u64 + random::<u32>() as u64;
+    let block_hashes: Vec<B256> = (0..5).map(|_| B256::random()).collect();
+
+    // Act
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+
+    for (i, block_hash) in block_hashes.iter().enumerate() {
+        let mut event = random_event(EventType::Deposit { to: Address::random(), value: U256::from(random::<u64>()) });
+        event.token_address = token_address;
+        event.block_number = first_block + i as u64;
+        event.block_hash = *block_hash;
+        tx.send(SubscriberMessage::Event(event)).await.unwrap();
+        tx.send(SubscriberMessage::BlockProcessed {
+            token_address,
+            block_number: first_block + i as u64,
+            block_hash: *block_hash,
+        })
+        .await
+        .unwrap();
+    }
+
+    // an event of another token in one of the reorganized blocks
+    let mut other_token_event =
+        random_event(EventType::Deposit { to: Address::random(), value: U256::from(random::<u64>()) });
+    other_token_event.block_number = first_block + 4;
+    let other_token_address = other_token_event.token_address;
+    tx.send(SubscriberMessage::Event(other_token_event)).await.unwrap();
+
+    tx.send(SubscriberMessage::ChainReorganized {
+        token_address,
+        block_number: first_block + 3,
+        parent_hash: block_hashes[2],
+    })
+    .await
+    .unwrap();
+
+    // Drop the sender to close the channel
+    drop(tx);
+
+    let mut persisted_events = vec![];
+    let mut reorganizations = vec![];
+    while let Some(response) = response_rx.recv().await {
+        match response {
+            StorageEvent::Persisted(event) => persisted_events.push(event.model().clone()),
+            StorageEvent::ChainReorganized { token_address, block_number, removed_events } => {
+                reorganizations.push((token_address, block_number, removed_events))
+            }
+        }
+    }
+
+    // Assert
+    assert_eq!(6, persisted_events.len());
+    assert_eq!(vec![(token_address, first_block + 3, 2)], reorganizations);
+
+    let fetched_events =
+        storage.fetch_all_events(None, Some(token_address), persisted_events[0].id, 100).await.unwrap();
+    assert_eq!(&persisted_events[..3], &fetched_events[..]);
+
+    // the events of the other token are not affected
+    let fetched_events =
+        storage.fetch_all_events(None, Some(other_token_address), persisted_events[0].id, 100).await.unwrap();
+    assert_eq!(&persisted_events[5..], &fetched_events[..]);
+
+    let checkpoint = storage.fetch_checkpoint(&token_address).await.unwrap().unwrap();
+    assert_eq!(first_block + 2, checkpoint.data.block_number);
+    assert_eq!(block_hashes[2], checkpoint.data.block_hash);
+}
+
+/// Tests that the balances are updated by the Deposit, Withdrawal and Transfer events, and only once per event
+#[tokio::test]
+async fn test_update_balances() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let token_address = Address::random();
+    let alice = Address::random();
+    let bob = Address::random();
+
+    let token_event = |event_type| {
+        let mut event_data = random_event_data(event_type);
+        event_data.token_address = token_address;
+        event_data.value = U256::from(100);
+        event_data
+    };
+
+    let deposit = token_event(EthEventType::Deposit { to: alice });
+    let mut transfer = token_event(EthEventType::Transfer { from: alice, to: bob });
+    transfer.value = U256::from(30);
+    let mut withdrawal = token_event(EthEventType::Withdrawal { from: bob });
+    withdrawal.value = U256::from(10);
+    let mint = token_event(EthEventType::Transfer { from: Address::ZERO, to: bob });
+    let approval = token_event(EthEventType::Approve { from: alice, to: bob });
+
+    // Act
+    for event_data in [deposit.clone(), transfer, withdrawal, mint, approval, deposit] {
+        storage.save_event(event_data).await.unwrap();
+    }
+
+    // Assert
+    assert_eq!(I256::try_from(70).unwrap(), storage.fetch_balance(&token_address, &alice).await.unwrap());
+    assert_eq!(I256::try_from(120).unwrap(), storage.fetch_balance(&token_address, &bob).await.unwrap());
+    assert_eq!(I256::ZERO, storage.fetch_balance(&token_address, &Address::ZERO).await.unwrap());
+    assert_eq!(I256::ZERO, storage.fetch_balance(&Address::random(), &alice).await.unwrap());
+
+    let top_holders = storage.fetch_top_holders(&token_address, 10).await.unwrap();
+    let top_holders: Vec<_> = top_holders.into_iter().map(|model| (model.data.address, model.data.balance)).collect();
+    assert_eq!(vec![(bob, I256::try_from(120).unwrap()), (alice, I256::try_from(70).unwrap())], top_holders);
+
+    let top_holders = storage.fetch_top_holders(&token_address, 1).await.unwrap();
+    assert_eq!(1, top_holders.len());
+    assert_eq!(bob, top_holders[0].data.address);
+}
+
+/// Tests that the events whose value would overflow a balance are persisted without changing the ledger
+#[tokio::test]
+async fn test_skip_balance_overflow() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+    let token_address = Address::random();
+    let alice = Address::random();
+    let bob = Address::random();
+
+    // Act
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+    let max_balance = U256::try_from(I256::MAX).unwrap();
+    for event_type in [
+        EventType::Transfer { from: alice, to: bob, value: U256::MAX },
+        EventType::Deposit { to: bob, value: max_balance },
+        EventType::Deposit { to: bob, value: U256::from(1) },
+        EventType::Withdrawal { from: alice, value: U256::from(5) },
+    ] {
+        let mut event = random_event(event_type);
+        event.token_address = token_address;
+        tx.send(SubscriberMessage::Event(event)).await.unwrap();
+    }
+    for _ in 0..4 {
+        let response = response_rx.recv().await.unwrap();
+        assert!(matches!(response, StorageEvent::Persisted(PersistedEvent::New(_))));
+    }
+
+    // Assert
+    assert_eq!(4, storage.fetch_all_events(None, Some(token_address), 0, 100).await.unwrap().len());
+    assert_eq!(I256::MAX, storage.fetch_balance(&token_address, &bob).await.unwrap());
+    assert_eq!(I256::try_from(-5).unwrap(), storage.fetch_balance(&token_address, &alice).await.unwrap());
+}
+
+/// Tests that a chain reorganization reverts the balance changes of the removed events
+#[tokio::test]
+async fn test_revert_balances_on_chain_reorganization() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+    let token_address = Address::random();
+    let alice = Address::random();
+    let bob = Address::random();
+
+    // Use blocks far beyond the ones generated by the other tests
+    let first_block = u32::MAX as u64 + random::<u32>() as u64;
+
+    let event_types = [
+        EventType::Deposit { to: alice, value: U256::from(100) },
+        EventType::Transfer { from: alice, to: bob, value: U256::from(40) },
+        EventType::Withdrawal { from: bob, value: U256::from(15) },
+    ];
+
+    // Act
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+
+    for (i, event_type) in event_types.into_iter().enumerate() {
+        let mut event = random_event(event_type);
+        event.token_address = token_address;
+        event.block_number = first_block + i as u64;
+        tx.send(SubscriberMessage::Event(event)).await.unwrap();
+    }
+
+    tx.send(SubscriberMessage::ChainReorganized {
+        token_address,
+        block_number: first_block + 1,
+        parent_hash: B256::random(),
+    })
+    .await
+    .unwrap();
+
+    // Drop the sender to close the channel
+    drop(tx);
+    while response_rx.recv().await.is_some() {}
+
+    // Assert
+    assert_eq!(I256::try_from(100).unwrap(), storage.fetch_balance(&token_address, &alice).await.unwrap());
+    assert_eq!(I256::ZERO, storage.fetch_balance(&token_address, &bob).await.unwrap());
+}
+
+/// Creates the details of a transaction sent by the given account
+fn transaction_from(from: Address) -> EthTransactionData {
+    EthTransactionData { from, to: None, gas_used: 21_000, effective_gas_price: 1, status: true }
+}
+
+/// Tests that the allowances are set by the Approval events and spent by the Transfer events sent by the spender
+#[tokio::test]
+async fn test_update_allowances() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let token_address = Address::random();
+    let owner = Address::random();
+    let spender = Address::random();
+    let infinite_spender = Address::random();
+
+    let token_event = |event_type, value: u64, sender: Option<Address>| {
+        let mut event_data = random_event_data(event_type);
+        event_data.token_address = token_address;
+        event_data.value = U256::from(value);
+        event_data.transaction = sender.map(transaction_from);
+        event_data
+    };
+
+    let events = [
+        token_event(EthEventType::Approve { from: owner, to: spender }, 100, None),
+        token_event(EthEventType::Approve { from: owner, to: infinite_spender }, 0, None),
+        // spent by the spender
+        token_event(EthEventType::Transfer { from: owner, to: Address::random() }, 30, Some(spender)),
+        // sent by the owner itself
+        token_event(EthEventType::Transfer { from: owner, to: Address::random() }, 20, Some(owner)),
+        // without the transaction details the sender is unknown
+        token_event(EthEventType::Transfer { from: owner, to: Address::random() }, 10, None),
+        // sent by an account without allowance
+        token_event(EthEventType::Transfer { from: owner, to: Address::random() }, 10, Some(Address::random())),
+    ];
+
+    // Act
+    for event_data in events {
+        storage.save_event(event_data).await.unwrap();
+    }
+    let mut infinite_approval = token_event(EthEventType::Approve { from: owner, to: infinite_spender }, 0, None);
+    infinite_approval.value = U256::MAX;
+    storage.save_event(infinite_approval).await.unwrap();
+    storage
+        .save_event(token_event(
+            EthEventType::Transfer { from: owner, to: Address::random() },
+            50,
+            Some(infinite_spender),
+        ))
+        .await
+        .unwrap();
+
+    // Assert
+    let allowances = storage.fetch_allowances(Some(token_address), &owner).await.unwrap();
+    let allowances: Vec<_> = allowances.into_iter().map(|model| (model.data.spender, model.data.allowance)).collect();
+    assert_eq!(vec![(spender, U256::from(70)), (infinite_spender, U256::MAX)], allowances);
+
+    assert_eq!(2, storage.fetch_allowances(None, &owner).await.unwrap().len());
+    assert!(storage.fetch_allowances(Some(Address::random()), &owner).await.unwrap().is_empty());
+    assert!(storage.fetch_allowances(Some(token_address), &spender).await.unwrap().is_empty());
+}
+
+/// Tests that a chain reorganization rebuilds the allowances changed by the removed events
+#[tokio::test]
+async fn test_rebuild_allowances_on_chain_reorganization() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+    let token_address = Address::random();
+    let owner = Address::random();
+    let spender = Address::random();
+    let other_spender = Address::random();
+
+    // Use blocks far beyond the ones generated by the other tests
+    let first_block = u32::MAX as u64 + random::<u32>() as u64;
+
+    let events = [
+        (EventType::Approval { from: owner, to: spender, value: U256::from(100) }, None),
+        (EventType::Transfer { from: owner, to: Address::random(), value: U256::from(30) }, Some(spender)),
+        (EventType::Approval { from: owner, to: spender, value: U256::from(500) }, None),
+        (EventType::Approval { from: owner, to: other_spender, value: U256::from(10) }, None),
+    ];
+
+    // Act
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+
+    for (i, (event_type, sender)) in events.into_iter().enumerate() {
+        let mut event = random_event(event_type);
+        event.token_address = token_address;
+        event.block_number = first_block + i as u64;
+        event.transaction = sender.map(|from| TransactionInfo {
+            from,
+            to: None,
+            gas_used: 21_000,
+            effective_gas_price: 1,
+            status: true,
+        });
+        tx.send(SubscriberMessage::Event(event)).await.unwrap();
+    }
+
+    tx.send(SubscriberMessage::ChainReorganized {
+        token_address,
+        block_number: first_block + 2,
+        parent_hash: B256::random(),
+    })
+    .await
+    .unwrap();
+
+    // Drop the sender to close the channel
+    drop(tx);
+    while response_rx.recv().await.is_some() {}
+
+    // Assert
+    let allowances = storage.fetch_allowances(Some(token_address), &owner).await.unwrap();
+    let allowances: Vec<_> = allowances.into_iter().map(|model| (model.data.spender, model.data.allowance)).collect();
+    assert_eq!(vec![(spender, U256::from(70))], allowances);
+}
+
+/// Tests that the ledger snapshot only counts the events persisted up to the checkpoint
+#[tokio::test]
+async fn test_fetch_ledger_snapshot_at_checkpoint() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+    let token_address = Address::random();
+    let alice = Address::random();
+    let bob = Address::random();
+    let carol = Address::random();
+
+    // Use blocks far beyond the ones generated by the other tests
+    let first_block = u32::MAX as u64 + random::<u32>() as u64;
+    let block_hash = B256::random();
+
+    // Act
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+
+    let mut deposit = random_event(EventType::Deposit { to: alice, value: U256::from(100) });
+    deposit.token_address = token_address;
+    deposit.block_number = first_block;
+    tx.send(SubscriberMessage::Event(deposit)).await.unwrap();
+    tx.send(SubscriberMessage::BlockProcessed { token_address, block_number: first_block, block_hash }).await.unwrap();
+
+    // the block of the transfer is not processed yet
+    let mut transfer = random_event(EventType::Transfer { from: alice, to: bob, value: U256::from(30) });
+    transfer.token_address = token_address;
+    transfer.block_number = first_block + 1;
+    tx.send(SubscriberMessage::Event(transfer)).await.unwrap();
+
+    // Drop the sender to close the channel
+    drop(tx);
+    while response_rx.recv().await.is_some() {}
+
+    let snapshot = storage.fetch_ledger_snapshot(&token_address, &[carol], 10).await.unwrap().unwrap();
+
+    // Assert
+    assert_eq!(first_block, snapshot.block_number);
+    assert_eq!(block_hash, snapshot.block_hash);
+    assert_eq!(vec![(carol, I256::ZERO), (alice, I256::try_from(100).unwrap()), (bob, I256::ZERO)], snapshot.balances);
+    assert_eq!(I256::try_from(100).unwrap(), snapshot.total_supply);
+
+    assert!(storage.fetch_ledger_snapshot(&Address::random(), &[carol], 10).await.unwrap().is_none());
+}
+
+/// Tests that the latest reconciliation of each token is returned
+#[tokio::test]
+async fn test_save_reconciliations() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let token_address = Address::random();
+    let other_token_address = Address::random();
+
+    let reconciliation = |token_address, block_number| ReconciliationData {
+        token_address,
+        block_number,
+        block_hash: B256::random(),
+        reconciled_at: block_number,
+        checked_addresses: 1,
+        discrepancies: vec![DiscrepancyData {
+            address: Some(Address::random()),
+            derived: I256::try_from(-1).unwrap(),
+            on_chain: U256::from(1),
+        }],
+    };
+
+    // Act
+    storage.save_reconciliation(reconciliation(token_address, 1)).await.unwrap();
+    let latest = storage.save_reconciliation(reconciliation(token_address, 2)).await.unwrap();
+    let other_latest = storage.save_reconciliation(reconciliation(other_token_address, 1)).await.unwrap();
+
+    // Assert
+    let reconciliations: Vec<_> = storage
+        .fetch_latest_reconciliations()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|model| [token_address, other_token_address].contains(&model.data.token_address))
+        .collect();
+    assert_eq!(vec![latest, other_latest], reconciliations);
+}
+
+/// Tests that the events can be filtered by the address of the emitting token
+#[tokio::test]
+async fn test_fetch_events_by_token() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let token_address = Address::random();
+
+    let mut token_events = vec![];
+    for i in 0..6 {
+        let mut event_data = if i % 2 == 0 {
+            random_event_data(EthEventType::Approve { from: Address::random(), to: Address::random() })
+        } else {
+            random_event_data(EthEventType::Transfer { from: Address::random(), to: Address::random() })
+        };
+        // events of the token interleaved with events of other tokens
+        storage.save_event(random_event_data(EthEventType::Deposit { to: Address::random() })).await.unwrap();
+        event_data.token_address = token_address;
+        token_events.push(storage.save_event(event_data).await.unwrap().model().clone());
+    }
+
+    // Act
+    let all_events = storage.fetch_all_events(None, Some(token_address), token_events[0].id, 100).await.unwrap();
+    let transfer_events = storage
+        .fetch_all_events(Some(EthEventTypeDiscriminants::Transfer), Some(token_address), token_events[0].id, 100)
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(token_events, all_events);
+    assert_eq!(token_events.iter().skip(1).step_by(2).cloned().collect::<Vec<_>>(), transfer_events);
+}
+
+/// Tests that the event stream stops receiving messages while the persisted events are not consumed,
+/// and resumes when they are
+#[tokio::test]
+async fn test_event_stream_applies_backpressure() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(5);
+    let events_count = 30;
+
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+
+    let sender = tokio::spawn(async move {
+        for _ in 0..events_count {
+            let event = random_event(EventType::Deposit { to: Address::random(), value: U256::from(random::<u64>()) });
+            tx.send(SubscriberMessage::Event(event)).await.unwrap();
+        }
+    });
+
+    // Act & Assert
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(!sender.is_finished());
+
+    let mut received_events = 0;
+    while received_events < events_count {
+        let response = response_rx.recv().await.unwrap();
+        assert!(matches!(response, StorageEvent::Persisted(PersistedEvent::New(_))));
+        received_events += 1;
+    }
+    sender.await.unwrap();
+    assert_eq!(0, storage.queue_depth());
+}
+
+/// Tests that the queue depth is updated while a batch is retried because the database is unreachable
+#[tokio::test]
+async fn test_queue_depth_while_the_database_is_unreachable() {
+    // Arrange
+    let pool = new_single_connection_pool().await;
+    let storage = StorageService::new(SqlxPgC3p0Pool::new(pool.clone())).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(10);
+    let (mut response_rx, _handle) = storage.subscribe_to_event_stream(rx);
+    let connection = pool.acquire().await.unwrap();
+
+    // Act
+    let event =
+        || SubscriberMessage::Event(random_event(EventType::Deposit { to: Address::random(), value: U256::from(1) }));
+    tx.send(event()).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    for _ in 0..4 {
+        tx.send(event()).await.unwrap();
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+    let depth_while_unreachable = storage.queue_depth();
+
+    drop(connection);
+    for _ in 0..5 {
+        let response = response_rx.recv().await.unwrap();
+        assert!(matches!(response, StorageEvent::Persisted(PersistedEvent::New(_))));
+    }
+
+    // Assert
+    assert_eq!(4, depth_while_unreachable);
+    assert_eq!(0, storage.queue_depth());
+}
+
+/// Tests that the metadata of a token is saved and replaced
+#[tokio::test]
+async fn test_save_token_metadata() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool).await.unwrap();
+    let token_address = Address::random();
+
+    // Act
+    storage
+        .save_token_metadata(TokenMetadata { token_address, name: None, symbol: None, decimals: None })
+        .await
+        .unwrap();
+    let saved = storage
+        .save_token_metadata(TokenMetadata {
+            token_address,
+            name: Some("Wrapped Ether".to_owned()),
+            symbol: Some("WETH".to_owned()),
+            decimals: Some(18),
+        })
+        .await
+        .unwrap();
+
+    // Assert
+    let metadata: Vec<_> = storage
+        .fetch_all_token_metadata()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|metadata| metadata.data.token_address == token_address)
+        .collect();
+    assert_eq!(vec![saved], metadata);
+    assert_eq!(Some("WETH".to_owned()), metadata[0].data.symbol);
+    assert_eq!(Some(18), metadata[0].data.decimals);
+}
+
+/// Tests that the events persisted by the first schema, without token and chain position, are archived
+/// so that the events of the current schema can still be fetched
+#[tokio::test]
+async fn test_archive_legacy_events() {
+    // Arrange
+    let pool = new_pg_pool().await;
+    let storage = StorageService::new(pool.clone()).await.unwrap();
+    let saved = storage.save_event(random_event_data(EthEventType::Deposit { to: Address::random() })).await.unwrap();
+    let legacy_data = format!(r#"{{"value":"0x64","event_type":{{"type":"Deposit","to":"{}"}}}}"#, Address::random());
+
+    // Act
+    let mut tx = pool.pool().begin().await.unwrap();
+    let (legacy_id,): (i64,) = sqlx::query_as(
+        "insert into ETH_EVENT (VERSION, create_epoch_millis, update_epoch_millis, DATA) values (0, 0, 0, $1::jsonb) returning ID",
+    )
+    .bind(&legacy_data)
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap();
+    sqlx::raw_sql(include_str!("../../resources/db/pg/migrations/00009_archive_legacy_eth_event.sql"))
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+
+    // Assert
+    let fetched_events = storage.fetch_all_events(None, None, saved.model().id, 1).await.unwrap();
+    assert_eq!(vec![saved.model().clone()], fetched_events);
+    let (archived,): (i64,) = sqlx::query_as("select count(*) from ETH_EVENT_LEGACY where ID = $1")
+        .bind(legacy_id)
+        .fetch_one(pool.pool())
+        .await
+        .unwrap();
+    assert_eq!(1, archived);
+}
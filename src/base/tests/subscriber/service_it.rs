@@ -1,36 +1,343 @@
-use std::{
-    str::FromStr,
-    sync::{Arc, atomic::AtomicBool},
+use alloy::{
+    primitives::{Address, B256, I256, LogData, U256},
+    rpc::types::Log,
+    sol,
+    sol_types::SolEvent,
 };
+use base::{
+    config::Token,
+    storage::service::StorageService,
+    subscriber::{
+        model::{ConnectionState, EventType, SubscriberMessage, TokenStandard},
+        replay::{LogRecorder, LogReplayer},
+        service::SubscriberService,
+    },
+};
+use rand::random;
+use tokio_util::sync::CancellationToken;
+
+use crate::{get_settings, storage::new_pg_pool};
 
-use alloy::primitives::Address;
-use base::subscriber::service::SubscriberService;
+sol! {
+    event Deposit(address indexed dst, uint256 wad);
+    event Transfer(address indexed src, address indexed dst, uint256 wad);
+}
 
-use crate::get_settings;
+/// A recording of the mainnet WETH token over four blocks, with a USDC transfer in between
+const WETH_RECORDING: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/resources/weth_logs.jsonl");
 
 #[tokio::test]
-async fn test_subscription_to_remote_node() {
+async fn test_subscription_to_recorded_node() {
     // Arrange
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1000);
     let settings = get_settings();
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-    let run_until = Arc::new(AtomicBool::new(true));
+    let token = Token { start_block: None, ..settings.eth_node.tokens[0].clone() };
+    let subscriber = SubscriberService::new(&settings.eth_node, &token);
+
+    // Act
+    let replayer = LogReplayer::open(WETH_RECORDING, None).unwrap();
+    let (status, _unconfirmed, handle) =
+        subscriber.replay(replayer, tx, CancellationToken::new()).await.expect("Failed to subscribe");
+    handle.await.unwrap().unwrap();
+
+    let mut messages = vec![];
+    while let Some(message) = rx.recv().await {
+        messages.push(message);
+    }
+
+    // Assert
+    let events: Vec<_> = messages
+        .iter()
+        .filter_map(|message| match message {
+            SubscriberMessage::Event(event) => Some(event),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(6, events.len());
+    assert!(events.iter().all(|event| event.token_address == token.address));
+    assert!(matches!(events[0].event_type, EventType::Deposit { .. }));
+    assert!(matches!(events[2].event_type, EventType::Approval { .. }));
+    assert!(matches!(events[3].event_type, EventType::Withdrawal { .. }));
 
-    let subscriber = SubscriberService::new(
-        settings.eth_node.wss_url,
-        5,
-        Address::from_str(&settings.eth_node.token_address).unwrap(),
+    let processed_blocks: Vec<_> = messages
+        .iter()
+        .filter_map(|message| match message {
+            SubscriberMessage::BlockProcessed { block_number, .. } => Some(*block_number),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vec![22_000_000, 22_000_001, 22_000_003, 22_000_004], processed_blocks);
+
+    assert_eq!(ConnectionState::Stopped, status.borrow().state);
+    assert_eq!(Some(22_000_004), status.borrow().last_block);
+    assert!(status.borrow().last_event_time.is_some());
+}
+
+/// A recording of the logs of a WETH token: a deposit, a transfer reorganized and replaced by another one,
+/// a duplicate of the deposit and a log of another contract
+struct Recording {
+    path: String,
+    token: Token,
+    alice: Address,
+    bob: Address,
+    block_hashes: [B256; 3],
+}
+
+/// Records the logs of a WETH token to a temporary file
+fn record_logs() -> Recording {
+    let token = weth_token();
+    let (alice, bob) = (Address::random(), Address::random());
+    let block_hashes = [B256::random(), B256::random(), B256::random()];
+    let first_block = u32::MAX as u64 + random::<u32>() as u64;
+
+    let deposit = log(
+        token.address,
+        Deposit { dst: alice, wad: U256::from(100) }.encode_log_data(),
+        first_block,
+        block_hashes[0],
+        0,
+    );
+    let transfer = log(
+        token.address,
+        Transfer { src: alice, dst: bob, wad: U256::from(40) }.encode_log_data(),
+        first_block + 1,
+        block_hashes[1],
+        0,
+    );
+    let removed_transfer = Log { removed: true, ..transfer.clone() };
+    let new_transfer = log(
+        token.address,
+        Transfer { src: alice, dst: bob, wad: U256::from(30) }.encode_log_data(),
+        first_block + 1,
+        block_hashes[2],
+        0,
     );
+    let other_contract_log = log(
+        Address::random(),
+        Deposit { dst: alice, wad: U256::from(1) }.encode_log_data(),
+        first_block + 1,
+        block_hashes[2],
+        1,
+    );
+
+    let path = record(&[deposit.clone(), transfer, removed_transfer, new_transfer, deposit, other_contract_log]);
+
+    Recording { path, token, alice, bob, block_hashes }
+}
+
+/// Returns the settings of a new WETH token
+fn weth_token() -> Token {
+    Token {
+        address: Address::random(),
+        standard: TokenStandard::Weth,
+        start_block: None,
+        abi_path: None,
+        filters: vec![],
+        ..get_settings().eth_node.tokens[0].clone()
+    }
+}
+
+/// Builds a canonical log of a new transaction
+fn log(address: Address, data: LogData, block_number: u64, block_hash: B256, log_index: u64) -> Log {
+    Log {
+        inner: alloy::primitives::Log { address, data },
+        block_hash: Some(block_hash),
+        block_number: Some(block_number),
+        block_timestamp: Some(block_number * 12),
+        transaction_hash: Some(B256::random()),
+        transaction_index: Some(0),
+        log_index: Some(log_index),
+        removed: false,
+    }
+}
+
+/// Records the given logs to a temporary file and returns its path
+fn record(logs: &[Log]) -> String {
+    let path = std::env::temp_dir().join(format!("recording-{}.jsonl", random::<u64>())).to_string_lossy().to_string();
+    let recorder = LogRecorder::create(&path).unwrap();
+    for log in logs {
+        recorder.record(log).unwrap();
+    }
+    path
+}
+
+/// Tests that the recorded logs are replayed as the messages of the live subscription
+#[tokio::test]
+async fn test_replay_recorded_logs() {
+    // Arrange
+    let settings = get_settings();
+    let recording = record_logs();
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1000);
+    let subscriber = SubscriberService::new(&settings.eth_node, &recording.token);
+
+    // Act
+    let replayer = LogReplayer::open(&recording.path, Some(1000.0)).unwrap();
+    let (status, _unconfirmed, handle) = subscriber.replay(replayer, tx, CancellationToken::new()).await.unwrap();
+    handle.await.unwrap().unwrap();
+
+    let mut messages = vec![];
+    while let Some(message) = rx.recv().await {
+        messages.push(message);
+    }
+
+    // Assert
+    assert_eq!(6, messages.len());
+    let token_address = recording.token.address;
+    let first_block = match &messages[0] {
+        SubscriberMessage::Event(event) => {
+            assert_eq!(EventType::Deposit { to: recording.alice, value: U256::from(100) }, event.event_type);
+            assert_eq!(event.block_number * 12, event.block_timestamp);
+            event.block_number
+        }
+        message => panic!("Unexpected message: {message:?}"),
+    };
+    assert_eq!(
+        SubscriberMessage::BlockProcessed {
+            token_address,
+            block_number: first_block,
+            block_hash: recording.block_hashes[0]
+        },
+        messages[1]
+    );
+    assert!(matches!(
+        &messages[2],
+        SubscriberMessage::Event(event) if event.block_hash == recording.block_hashes[1]
+    ));
+    assert_eq!(
+        SubscriberMessage::ChainReorganized {
+            token_address,
+            block_number: first_block + 1,
+            parent_hash: recording.block_hashes[0]
+        },
+        messages[3]
+    );
+    match &messages[4] {
+        SubscriberMessage::Event(event) => {
+            assert_eq!(
+                EventType::Transfer { from: recording.alice, to: recording.bob, value: U256::from(30) },
+                event.event_type
+            );
+            assert_eq!(recording.block_hashes[2], event.block_hash);
+        }
+        message => panic!("Unexpected message: {message:?}"),
+    }
+    assert_eq!(
+        SubscriberMessage::BlockProcessed {
+            token_address,
+            block_number: first_block + 1,
+            block_hash: recording.block_hashes[2]
+        },
+        messages[5]
+    );
+    assert_eq!(ConnectionState::Stopped, status.borrow().state);
+    assert_eq!(Some(first_block + 1), status.borrow().last_block);
+
+    std::fs::remove_file(&recording.path).unwrap();
+}
+
+/// Tests that the replayed logs are persisted by the storage pipeline
+#[tokio::test]
+async fn test_replay_recorded_logs_to_storage() {
+    // Arrange
+    let settings = get_settings();
+    let recording = record_logs();
+    let storage = StorageService::new(new_pg_pool().await).await.unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+    let subscriber = SubscriberService::new(&settings.eth_node, &recording.token);
 
     // Act
-    let run_until_clone = run_until.clone();
-    subscriber.subscribe_to(tx, run_until_clone).await.expect("Failed to subscribe");
+    let (_response_rx, storage_handle) = storage.subscribe_to_event_stream(rx);
+    let replayer = LogReplayer::open(&recording.path, None).unwrap();
+    let (_status, _unconfirmed, handle) = subscriber.replay(replayer, tx, CancellationToken::new()).await.unwrap();
+    handle.await.unwrap().unwrap();
+    storage_handle.await.unwrap();
 
     // Assert
-    // wait for 5 events
-    for _ in 0..5 {
-        let event = rx.recv().await.unwrap();
-        println!("Received event: {event:?}");
+    let token_address = recording.token.address;
+    let events = storage.fetch_all_events(None, Some(token_address), 0, 100).await.unwrap();
+    assert_eq!(2, events.len());
+    assert_eq!(I256::try_from(70).unwrap(), storage.fetch_balance(&token_address, &recording.alice).await.unwrap());
+    assert_eq!(I256::try_from(30).unwrap(), storage.fetch_balance(&token_address, &recording.bob).await.unwrap());
+
+    let checkpoint = storage.fetch_checkpoint(&token_address).await.unwrap().unwrap();
+    assert_eq!(recording.block_hashes[2], checkpoint.data.block_hash);
+
+    std::fs::remove_file(&recording.path).unwrap();
+}
+
+/// Tests that a known block replaced by a block with a new hash, without any removed log, is rolled back
+/// from the storage
+#[tokio::test]
+async fn test_replay_replaced_block_to_storage() {
+    // Arrange
+    let settings = get_settings();
+    let token = weth_token();
+    let (alice, bob) = (Address::random(), Address::random());
+    let block_hashes = [B256::random(), B256::random(), B256::random()];
+    let first_block = u32::MAX as u64 + random::<u32>() as u64;
+    let path = record(&[
+        log(
+            token.address,
+            Deposit { dst: alice, wad: U256::from(100) }.encode_log_data(),
+            first_block,
+            block_hashes[0],
+            0,
+        ),
+        log(
+            token.address,
+            Transfer { src: alice, dst: bob, wad: U256::from(40) }.encode_log_data(),
+            first_block + 1,
+            block_hashes[1],
+            0,
+        ),
+        log(
+            token.address,
+            Transfer { src: alice, dst: bob, wad: U256::from(30) }.encode_log_data(),
+            first_block + 1,
+            block_hashes[2],
+            0,
+        ),
+    ]);
+
+    let storage = StorageService::new(new_pg_pool().await).await.unwrap();
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1000);
+    let (storage_tx, storage_rx) = tokio::sync::mpsc::channel(1000);
+    let subscriber = SubscriberService::new(&settings.eth_node, &token);
+
+    // Act
+    let (_response_rx, storage_handle) = storage.subscribe_to_event_stream(storage_rx);
+    let replayer = LogReplayer::open(&path, None).unwrap();
+    let (_status, _unconfirmed, handle) = subscriber.replay(replayer, tx, CancellationToken::new()).await.unwrap();
+    let mut messages = vec![];
+    while let Some(message) = rx.recv().await {
+        messages.push(message.clone());
+        storage_tx.send(message).await.unwrap();
     }
+    drop(storage_tx);
+    handle.await.unwrap().unwrap();
+    storage_handle.await.unwrap();
+
+    // Assert
+    let reorganizations: Vec<_> =
+        messages.iter().filter(|message| matches!(message, SubscriberMessage::ChainReorganized { .. })).collect();
+    assert_eq!(
+        vec![&SubscriberMessage::ChainReorganized {
+            token_address: token.address,
+            block_number: first_block + 1,
+            parent_hash: block_hashes[0]
+        }],
+        reorganizations
+    );
+
+    let events = storage.fetch_all_events(None, Some(token.address), 0, 100).await.unwrap();
+    assert_eq!(2, events.len());
+    assert!(events.iter().all(|event| event.data.block_hash != block_hashes[1]));
+    assert_eq!(I256::try_from(70).unwrap(), storage.fetch_balance(&token.address, &alice).await.unwrap());
+    assert_eq!(I256::try_from(30).unwrap(), storage.fetch_balance(&token.address, &bob).await.unwrap());
+
+    let checkpoint = storage.fetch_checkpoint(&token.address).await.unwrap().unwrap();
+    assert_eq!(first_block + 1, checkpoint.data.block_number);
+    assert_eq!(block_hashes[2], checkpoint.data.block_hash);
 
-    run_until.store(false, std::sync::atomic::Ordering::Relaxed);
+    std::fs::remove_file(&path).unwrap();
 }
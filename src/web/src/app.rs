@@ -1,5 +1,6 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
+use alloy::primitives::{Address, utils::format_units};
 use axum::{
     Json, Router,
     extract::{Query, State},
@@ -9,24 +10,52 @@ use axum::{
 use base::{
     error::CoreError,
     storage::{
-        model::{EthEventModel, EthEventTypeDiscriminants},
+        model::{EthEventModel, EthEventTypeDiscriminants, ReconciliationModel, TokenMetadataModel},
         service::StorageService,
     },
+    subscriber::model::{Event, StatusHandle, SubscriberStatus, UnconfirmedHandle},
 };
 use log::*;
-use serde::Deserialize;
-
-/// Creates a new Axum `Router` with a route for fetching logs.
-pub fn create_app<P: 'static + LogProvider + Send + Sync>(state: Arc<P>) -> Router {
-    Router::new().route("/api/v1/logs", get(get_logs)).with_state(state)
+use serde::{Deserialize, Serialize};
+
+/// Creates a new Axum `Router` with a route for fetching logs, a route for the latest reconciliation report,
+/// a route for the status of the subscriptions and of the storage queue and a route for the events waiting for
+/// confirmation.
+pub fn create_app<P: 'static + LogProvider + Send + Sync>(
+    state: Arc<P>,
+    statuses: Vec<StatusHandle>,
+    unconfirmed: Vec<UnconfirmedHandle>,
+) -> Router {
+    Router::new()
+        .route("/api/v1/logs", get(get_logs))
+        .route("/api/v1/reconciliation", get(get_reconciliation))
+        .with_state(state.clone())
+        .merge(Router::new().route("/api/v1/status", get(get_status)).with_state((state, Arc::new(statuses))))
+        .merge(Router::new().route("/api/v1/unconfirmed", get(get_unconfirmed)).with_state(Arc::new(unconfirmed)))
 }
 
 /// Query parameters for the get logs endpoint
 #[derive(Deserialize)]
 struct LogQuery {
     event_type: Option<EthEventTypeDiscriminants>,
+    token_address: Option<Address>,
     from_id: Option<u64>,
     max: Option<u32>,
+    decimals: Option<bool>,
+}
+
+/// A log returned by the get logs endpoint
+#[derive(Serialize)]
+struct LogResponse {
+    #[serde(flatten)]
+    event: EthEventModel,
+    /// The symbol of the token, returned only if the decimal amounts are requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    symbol: Option<String>,
+    /// The value scaled by the decimals of the token, returned only if the decimal amounts are requested
+    /// and the decimals of the token are known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount: Option<String>,
 }
 
 /// /api/v1/logs GET endpoint
@@ -35,8 +64,11 @@ struct LogQuery {
 ///
 /// The following optional query parameters are supported:
 /// - `event_type`: The type of event to filter by
+/// - `token_address`: The address of the token contract to filter by
 /// - `from_id`: The id of the first log to return. Defaults to 0
 /// - `max`: The maximum number of logs to return. Defaults to 10. Maximum value is 100
+/// - `decimals`: If true, the symbol of the token and the value scaled by the token decimals are returned
+///   next to the raw value. Defaults to false
 ///
 /// # Errors
 ///
@@ -51,8 +83,34 @@ async fn get_logs<P: 'static + LogProvider + Send + Sync>(
 
     debug!("get_logs - Fetching logs from id: {}, max: {}", from_id, max);
 
-    state
-        .fetch_all_events(query.event_type, from_id, max)
+    let fetch_logs = async {
+        let events = state.fetch_all_events(query.event_type, query.token_address, from_id, max).await?;
+        if !query.decimals.unwrap_or(false) {
+            return Ok(events.into_iter().map(|event| LogResponse { event, symbol: None, amount: None }).collect());
+        }
+
+        let metadata: HashMap<_, _> = state
+            .fetch_all_token_metadata()
+            .await?
+            .into_iter()
+            .map(|metadata| (metadata.data.token_address, metadata.data))
+            .collect();
+        Ok::<_, CoreError>(
+            events
+                .into_iter()
+                .map(|event| {
+                    let metadata = metadata.get(&event.data.token_address);
+                    let symbol = metadata.and_then(|metadata| metadata.symbol.clone());
+                    let amount = metadata
+                        .and_then(|metadata| metadata.decimals)
+                        .and_then(|decimals| format_units(event.data.value, decimals).ok());
+                    LogResponse { event, symbol, amount }
+                })
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    fetch_logs
         .await
         .map_err(|err: CoreError| {
             error!("Failed to fetch logs: {err:?}");
@@ -61,25 +119,114 @@ async fn get_logs<P: 'static + LogProvider + Send + Sync>(
         .map(Json)
 }
 
+/// /api/v1/reconciliation GET endpoint
+///
+/// Returns the latest reconciliation of each token with its contract in JSON format, including the balances
+/// that differ from the on-chain ones.
+///
+/// # Errors
+///
+/// Returns a `500 Internal Server Error` if there is an error interacting with the database.
+async fn get_reconciliation<P: 'static + LogProvider + Send + Sync>(State(state): State<Arc<P>>) -> impl IntoResponse {
+    debug!("get_reconciliation - Fetching the latest reconciliations");
+
+    state
+        .fetch_latest_reconciliations()
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch the reconciliations: {err:?}");
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        })
+        .map(Json)
+}
+
+/// The status returned by the status endpoint
+#[derive(Serialize)]
+struct StatusResponse {
+    /// The number of messages waiting to be persisted
+    queue_depth: usize,
+    subscriptions: Vec<SubscriberStatus>,
+}
+
+/// /api/v1/status GET endpoint
+///
+/// Returns the number of messages waiting to be persisted and the current status of the subscription of each
+/// token in JSON format.
+async fn get_status<P: 'static + LogProvider + Send + Sync>(
+    State((state, statuses)): State<(Arc<P>, Arc<Vec<StatusHandle>>)>,
+) -> Json<StatusResponse> {
+    Json(StatusResponse {
+        queue_depth: state.queue_depth(),
+        subscriptions: statuses.iter().map(|status| status.borrow().clone()).collect(),
+    })
+}
+
+/// The events of a token waiting for the confirmation of their block
+#[derive(Serialize)]
+struct UnconfirmedEvents {
+    token_address: Address,
+    events: Vec<Event>,
+}
+
+/// /api/v1/unconfirmed GET endpoint
+///
+/// Returns the events of each token that are waiting for the confirmation of their block, in chain order,
+/// in JSON format. The events are empty if no finality mode or number of confirmations is configured.
+async fn get_unconfirmed(State(unconfirmed): State<Arc<Vec<UnconfirmedHandle>>>) -> Json<Vec<UnconfirmedEvents>> {
+    Json(
+        unconfirmed
+            .iter()
+            .map(|handle| UnconfirmedEvents { token_address: handle.token_address(), events: handle.events() })
+            .collect(),
+    )
+}
+
 /// Trait for fetching logs from the storage
 pub trait LogProvider {
     /// Fetches a list of logs from the storage
     fn fetch_all_events(
         &self,
         event_type: Option<EthEventTypeDiscriminants>,
+        token_address: Option<Address>,
         from_id: u64,
         limit: u32,
     ) -> impl std::future::Future<Output = Result<Vec<EthEventModel>, CoreError>> + Send;
+
+    /// Fetches the metadata of all the tokens from the storage
+    fn fetch_all_token_metadata(
+        &self,
+    ) -> impl std::future::Future<Output = Result<Vec<TokenMetadataModel>, CoreError>> + Send;
+
+    /// Fetches the latest reconciliation of each token from the storage
+    fn fetch_latest_reconciliations(
+        &self,
+    ) -> impl std::future::Future<Output = Result<Vec<ReconciliationModel>, CoreError>> + Send;
+
+    /// Returns the number of messages waiting to be persisted
+    fn queue_depth(&self) -> usize;
 }
 
 impl LogProvider for StorageService {
     async fn fetch_all_events(
         &self,
         event_type: Option<EthEventTypeDiscriminants>,
+        token_address: Option<Address>,
         from_id: u64,
         limit: u32,
     ) -> Result<Vec<EthEventModel>, CoreError> {
-        self.fetch_all_events(event_type, from_id, limit).await
+        self.fetch_all_events(event_type, token_address, from_id, limit).await
+    }
+
+    async fn fetch_all_token_metadata(&self) -> Result<Vec<TokenMetadataModel>, CoreError> {
+        self.fetch_all_token_metadata().await
+    }
+
+    async fn fetch_latest_reconciliations(&self) -> Result<Vec<ReconciliationModel>, CoreError> {
+        self.fetch_latest_reconciliations().await
+    }
+
+    fn queue_depth(&self) -> usize {
+        self.queue_depth()
     }
 }
 
@@ -88,11 +235,13 @@ mod tests {
 
     use std::sync::Arc;
 
-    use alloy::primitives::{Address, U256};
+    use alloy::primitives::{B256, U256};
     use axum::body::Body;
     use axum::http::{Method, Request, StatusCode, header};
 
-    use base::storage::model::{EthEventData, EthEventType};
+    use alloy::primitives::I256;
+    use base::storage::model::{DiscrepancyData, EthEventData, EthEventType, ReconciliationData, TokenMetadataData};
+    use base::subscriber::model::{ConnectionState, EventType, SubscriberMessage, UnconfirmedMessages};
     use http_body_util::BodyExt; // for `collect`
     use tower::ServiceExt; // for `call`, `oneshot`, and `ready`
 
@@ -105,11 +254,12 @@ mod tests {
     impl LogProvider for TestLogProvider {
         /// A test implementation of the `fetch_all_events` method for testing the web server endpoints.
         ///
-        /// This method returns a vector of `EthEventModel` instances, with the id, value and event_type fields
-        /// populated with random values.
+        /// This method returns a vector of `EthEventModel` instances, with the id, value, event_type and
+        /// chain position fields populated with random values. The token address is the requested one, if any.
         async fn fetch_all_events(
             &self,
             event_type: Option<EthEventTypeDiscriminants>,
+            token_address: Option<Address>,
             from_id: u64,
             limit: u32,
         ) -> Result<Vec<EthEventModel>, CoreError> {
@@ -121,6 +271,7 @@ mod tests {
                     create_epoch_millis: 0,
                     update_epoch_millis: 0,
                     data: EthEventData {
+                        token_address: token_address.unwrap_or_default(),
                         value: U256::from(id),
                         event_type: event_type
                             .map(|typ| match typ {
@@ -134,6 +285,40 @@ mod tests {
                                 EthEventTypeDiscriminants::Withdrawal => {
                                     EthEventType::Withdrawal { from: Address::random() }
                                 }
+                                EthEventTypeDiscriminants::NftApproval => EthEventType::NftApproval {
+                                    owner: Address::random(),
+                                    approved: Address::random(),
+                                    token_id: U256::from(id),
+                                },
+                                EthEventTypeDiscriminants::NftTransfer => EthEventType::NftTransfer {
+                                    from: Address::random(),
+                                    to: Address::random(),
+                                    token_id: U256::from(id),
+                                },
+                                EthEventTypeDiscriminants::ApprovalForAll => EthEventType::ApprovalForAll {
+                                    owner: Address::random(),
+                                    operator: Address::random(),
+                                    approved: true,
+                                },
+                                EthEventTypeDiscriminants::TransferSingle => EthEventType::TransferSingle {
+                                    operator: Address::random(),
+                                    from: Address::random(),
+                                    to: Address::random(),
+                                    id: U256::from(id),
+                                },
+                                EthEventTypeDiscriminants::TransferBatch => EthEventType::TransferBatch {
+                                    operator: Address::random(),
+                                    from: Address::random(),
+                                    to: Address::random(),
+                                    ids: vec![U256::from(id)],
+                                    values: vec![U256::from(1)],
+                                },
+                                EthEventTypeDiscriminants::Uri => {
+                                    EthEventType::Uri { id: U256::from(id), value: format!("ipfs://{id}") }
+                                }
+                                EthEventTypeDiscriminants::Generic => {
+                                    EthEventType::Generic { name: "Sync".to_owned(), params: vec![] }
+                                }
                             })
                             .unwrap_or_else(|| match id % 4 {
                                 0 => EthEventType::Approve { from: Address::random(), to: Address::random() },
@@ -141,18 +326,68 @@ mod tests {
                                 2 => EthEventType::Deposit { to: Address::random() },
                                 _ => EthEventType::Withdrawal { from: Address::random() },
                             }),
+                        block_number: id,
+                        block_hash: B256::random(),
+                        block_timestamp: id,
+                        transaction_hash: B256::random(),
+                        transaction_index: 0,
+                        log_index: id,
+                        transaction: None,
                     },
                 })
                 .collect();
             Ok(logs)
         }
+
+        /// Returns the metadata of the default token address, used by the logs without a requested token address
+        async fn fetch_all_token_metadata(&self) -> Result<Vec<TokenMetadataModel>, CoreError> {
+            Ok(vec![TokenMetadataModel {
+                id: 0,
+                version: 0,
+                create_epoch_millis: 0,
+                update_epoch_millis: 0,
+                data: TokenMetadataData {
+                    token_address: Address::default(),
+                    name: Some("Test Token".to_owned()),
+                    symbol: Some("TST".to_owned()),
+                    decimals: Some(18),
+                },
+            }])
+        }
+
+        /// Returns a reconciliation of the default token address with a discrepancy of the total supply
+        async fn fetch_latest_reconciliations(&self) -> Result<Vec<ReconciliationModel>, CoreError> {
+            Ok(vec![ReconciliationModel {
+                id: 0,
+                version: 0,
+                create_epoch_millis: 0,
+                update_epoch_millis: 0,
+                data: ReconciliationData {
+                    token_address: Address::default(),
+                    block_number: 100,
+                    block_hash: B256::default(),
+                    reconciled_at: 0,
+                    checked_addresses: 10,
+                    discrepancies: vec![DiscrepancyData {
+                        address: None,
+                        derived: I256::try_from(-1).unwrap(),
+                        on_chain: U256::from(1),
+                    }],
+                },
+            }])
+        }
+
+        /// Returns a fixed number of messages waiting to be persisted
+        fn queue_depth(&self) -> usize {
+            7
+        }
     }
 
     /// Test that the `/api/v1/logs` endpoint returns the expected logs when no query parameters are provided
     #[tokio::test]
     async fn test_app_return_logs_with_default_query_values() {
         // Arrange
-        let app = create_app(Arc::new(TestLogProvider {}));
+        let app = create_app(Arc::new(TestLogProvider {}), vec![], vec![]);
 
         // Act
         let response = app
@@ -193,7 +428,7 @@ mod tests {
     #[tokio::test]
     async fn test_app_return_logs_with_custom_query_values() {
         // Arrange
-        let app = create_app(Arc::new(TestLogProvider {}));
+        let app = create_app(Arc::new(TestLogProvider {}), vec![], vec![]);
 
         // Act
         let response = app
@@ -226,7 +461,7 @@ mod tests {
     #[tokio::test]
     async fn test_app_return_max_100_logs() {
         // Arrange
-        let app = create_app(Arc::new(TestLogProvider {}));
+        let app = create_app(Arc::new(TestLogProvider {}), vec![], vec![]);
 
         // Act
         let response = app
@@ -248,4 +483,240 @@ mod tests {
 
         assert_eq!(body.len(), 100);
     }
+
+    /// Test that the `/api/v1/logs` endpoint exposes the chain position of each log
+    #[tokio::test]
+    async fn test_app_return_logs_with_chain_position() {
+        // Arrange
+        let app = create_app(Arc::new(TestLogProvider {}), vec![], vec![]);
+
+        // Act
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(Method::GET)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .uri("/api/v1/logs?max=1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let data = &body[0]["data"];
+        for field in
+            ["block_number", "block_hash", "block_timestamp", "transaction_hash", "transaction_index", "log_index"]
+        {
+            assert!(!data[field].is_null(), "missing field {field}");
+        }
+    }
+
+    /// Test that the `/api/v1/logs` endpoint filters the logs by token address
+    #[tokio::test]
+    async fn test_app_return_logs_of_token() {
+        // Arrange
+        let app = create_app(Arc::new(TestLogProvider {}), vec![], vec![]);
+        let token_address = Address::random();
+
+        // Act
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(Method::GET)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .uri(format!("/api/v1/logs?token_address={token_address}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Vec<EthEventModel> = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.len(), 10);
+        for log in body {
+            assert_eq!(token_address, log.data.token_address);
+        }
+    }
+
+    /// Test that the `/api/v1/logs` endpoint filters the NFT events by type
+    #[tokio::test]
+    async fn test_app_return_nft_logs() {
+        // Arrange
+        let app = create_app(Arc::new(TestLogProvider {}), vec![], vec![]);
+
+        // Act
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(Method::GET)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .uri("/api/v1/logs?event_type=TransferBatch")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Vec<EthEventModel> = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.len(), 10);
+        for log in body {
+            assert_eq!(EthEventTypeDiscriminants::TransferBatch, log.data.event_type.clone().into());
+        }
+    }
+
+    /// Test that the `/api/v1/status` endpoint returns the queue depth and the current status of each subscription
+    #[tokio::test]
+    async fn test_app_return_status() {
+        // Arrange
+        let token_address = Address::random();
+        let (status_tx, status_rx) = tokio::sync::watch::channel(SubscriberStatus {
+            token_address,
+            name: "WETH".to_owned(),
+            state: ConnectionState::Connecting,
+            endpoint: "wss://node".to_owned(),
+            last_block: None,
+            last_event_time: None,
+            reconnects: 0,
+            decode_errors: 0,
+        });
+        let app = create_app(Arc::new(TestLogProvider {}), vec![status_rx], vec![]);
+
+        status_tx.send_modify(|status| {
+            status.state = ConnectionState::Connected;
+            status.last_block = Some(100);
+            status.reconnects = 2;
+        });
+
+        // Act
+        let response = app
+            .oneshot(Request::builder().method(Method::GET).uri("/api/v1/status").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(7, body["queue_depth"]);
+        let subscriptions = &body["subscriptions"];
+        assert_eq!(1, subscriptions.as_array().unwrap().len());
+        assert_eq!(serde_json::json!(token_address), subscriptions[0]["token_address"]);
+        assert_eq!("connected", subscriptions[0]["state"]);
+        assert_eq!(100, subscriptions[0]["last_block"]);
+        assert_eq!(2, subscriptions[0]["reconnects"]);
+        assert_eq!(0, subscriptions[0]["decode_errors"]);
+    }
+
+    /// Test that the `/api/v1/unconfirmed` endpoint returns the events of each token waiting for confirmation
+    #[tokio::test]
+    async fn test_app_return_unconfirmed_events() {
+        // Arrange
+        let token_address = Address::random();
+        let to = Address::random();
+        let pending = UnconfirmedMessages::default();
+        let event = Event {
+            token_address,
+            block_number: 100,
+            block_hash: B256::random(),
+            block_timestamp: 1_700_000_000,
+            transaction_hash: B256::random(),
+            transaction_index: 1,
+            log_index: 2,
+            event_type: EventType::Deposit { to, value: U256::from(5) },
+            transaction: None,
+        };
+        pending.lock().unwrap().push_back((100, SubscriberMessage::Event(event)));
+        pending.lock().unwrap().push_back((
+            100,
+            SubscriberMessage::BlockProcessed { token_address, block_number: 100, block_hash: B256::random() },
+        ));
+        let app =
+            create_app(Arc::new(TestLogProvider {}), vec![], vec![UnconfirmedHandle::new(token_address, pending)]);
+
+        // Act
+        let response = app
+            .oneshot(Request::builder().method(Method::GET).uri("/api/v1/unconfirmed").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(1, body.as_array().unwrap().len());
+        assert_eq!(serde_json::json!(token_address), body[0]["token_address"]);
+        assert_eq!(1, body[0]["events"].as_array().unwrap().len());
+        assert_eq!(100, body[0]["events"][0]["block_number"]);
+        assert_eq!("Deposit", body[0]["events"][0]["event_type"]["type"]);
+        assert_eq!(serde_json::json!(to), body[0]["events"][0]["event_type"]["to"]);
+    }
+
+    /// Test that the `/api/v1/reconciliation` endpoint returns the latest reconciliation of each token
+    #[tokio::test]
+    async fn test_app_return_reconciliation() {
+        // Arrange
+        let app = create_app(Arc::new(TestLogProvider {}), vec![], vec![]);
+
+        // Act
+        let response = app
+            .oneshot(Request::builder().method(Method::GET).uri("/api/v1/reconciliation").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Vec<ReconciliationModel> = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(1, body.len());
+        assert_eq!(100, body[0].data.block_number);
+        assert_eq!(10, body[0].data.checked_addresses);
+        assert_eq!(None, body[0].data.discrepancies[0].address);
+        assert_eq!(I256::try_from(-1).unwrap(), body[0].data.discrepancies[0].derived);
+    }
+
+    /// Test that the `/api/v1/logs` endpoint returns the decimal amounts and the token symbol when requested
+    #[tokio::test]
+    async fn test_app_return_logs_with_decimal_amounts() {
+        // Arrange
+        let app = create_app(Arc::new(TestLogProvider {}), vec![], vec![]);
+
+        // Act
+        let request = |uri: &str| Request::builder().method(Method::GET).uri(uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request("/api/v1/logs?from_id=5&decimals=true")).await.unwrap();
+        let raw_response = app.oneshot(request("/api/v1/logs?from_id=5")).await.unwrap();
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(5, body[0]["id"]);
+        assert_eq!("TST", body[0]["symbol"]);
+        assert_eq!("0.000000000000000005", body[0]["amount"]);
+        assert_eq!("0x5", body[0]["data"]["value"]);
+
+        let raw_body = raw_response.into_body().collect().await.unwrap().to_bytes();
+        let raw_body: serde_json::Value = serde_json::from_slice(&raw_body).unwrap();
+        assert!(raw_body[0].get("symbol").is_none());
+        assert!(raw_body[0].get("amount").is_none());
+    }
 }
@@ -1,8 +1,10 @@
-use base::{config::Settings, storage, subscriber};
+use base::{config::Settings, enricher, reconciler, storage, subscriber};
 use c3p0::sqlx::SqlxPgC3p0Pool;
-use log::info;
+use log::{error, info, warn};
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use std::sync::Arc;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use web::app::create_app;
 
 /// Main entry point of the web server
@@ -19,14 +21,11 @@ async fn main() {
 
     info!("Starting the web server...");
 
-    // Initialize the services
-    let log_provider = {
-        let subscriber_service = subscriber::service::SubscriberService::new(
-            settings.eth_node.wss_url,
-            settings.eth_node.timeout_seconds,
-            settings.eth_node.token_address.parse().unwrap(),
-        );
+    // Cancelled on SIGINT or SIGTERM, or when a subscription fails, to stop the subscriptions
+    let shutdown = CancellationToken::new();
 
+    // Initialize the services
+    let (log_provider, statuses, unconfirmed, mut subscriptions, enricher_handle, storage_handle, reconciler_handle) = {
         // Connect to the database
         let options = PgConnectOptions::new()
             .username(&settings.database.username)
@@ -41,25 +40,197 @@ async fn main() {
             .await
             .expect("Failed to create database connection pool");
 
-        let storage_service = storage::service::StorageService::new(SqlxPgC3p0Pool::new(pool))
-            .await
-            .expect("Failed to initialize storage service");
+        let storage_service = Arc::new(
+            storage::service::StorageService::new(SqlxPgC3p0Pool::new(pool))
+                .await
+                .expect("Failed to initialize storage service"),
+        );
+
+        // Subscribe to the Ethereum events of each token from a remote node
+        let (sender, receiver) = tokio::sync::mpsc::channel(settings.pipeline.channel_capacity.max(1));
+        let mut subscriptions = JoinSet::new();
+        let mut statuses = vec![];
+        let mut unconfirmed = vec![];
+
+        // Replay the recorded logs instead of connecting to the node, if requested
+        let replayer = settings.eth_node.replay_path.as_deref().map(|replay_path| {
+            subscriber::replay::LogReplayer::open(replay_path, settings.eth_node.replay_speed)
+                .expect("Failed to read the recorded logs")
+        });
+        let recorder = settings.eth_node.record_path.as_deref().map(|record_path| {
+            subscriber::replay::LogRecorder::create(record_path).expect("Failed to open the recording file")
+        });
+
+        for token in &settings.eth_node.tokens {
+            let mut subscriber_service = subscriber::service::SubscriberService::new(&settings.eth_node, token);
+
+            let (status, unconfirmed_handle, subscription) = if let Some(replayer) = &replayer {
+                subscriber_service
+                    .replay(replayer.clone(), sender.clone(), shutdown.clone())
+                    .await
+                    .expect("Failed to replay the recorded logs")
+            } else {
+                // Discover the token metadata, keeping the persisted one if a call fails for any other reason than a revert
+                match subscriber_service.fetch_metadata().await {
+                    Ok(metadata) => {
+                        storage_service.save_token_metadata(metadata).await.expect("Failed to save token metadata");
+                    }
+                    Err(err) => warn!("Failed to fetch the metadata of token {}: {err:?}", token.address),
+                }
+
+                // Resume from the last persisted checkpoint, if any
+                if let Some(checkpoint) =
+                    storage_service.fetch_checkpoint(&token.address).await.expect("Failed to fetch checkpoint")
+                {
+                    subscriber_service.resume_from(checkpoint.data.block_number, checkpoint.data.block_hash);
+                }
+
+                if let Some(recorder) = &recorder {
+                    subscriber_service.record_to(recorder.clone());
+                }
+
+                subscriber_service
+                    .subscribe_to(sender.clone(), shutdown.clone())
+                    .await
+                    .expect("Failed to subscribe to Ethereum logs")
+            };
+            subscriptions.spawn(async move { subscription.await? });
+            statuses.push(status);
+            unconfirmed.push(unconfirmed_handle);
+        }
+
+        // Enrich the events with the details of their transaction before they are persisted
+        let (receiver, enricher_handle) = if settings.enrichment.enabled {
+            let (enriched_sender, enriched_receiver) =
+                tokio::sync::mpsc::channel(settings.pipeline.channel_capacity.max(1));
+            let enricher_handle = enricher::service::EnricherService::new(&settings.eth_node, &settings.enrichment)
+                .subscribe_to(receiver, enriched_sender, shutdown.clone());
+            (enriched_receiver, Some(enricher_handle))
+        } else {
+            (receiver, None)
+        };
 
-        // Subscribe to Ethereum events from a remote node
-        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
-        let run_until = Arc::new(std::sync::atomic::AtomicBool::new(true));
-        subscriber_service.subscribe_to(sender, run_until.clone()).await.expect("Failed to subscribe to Ethereum logs");
+        // Subscribe the storage service to internal event stream.
+        // The persisted events are not consumed, so the response receiver is dropped.
+        // The storage task terminates when all the subscriptions, and so all the senders, are dropped.
+        let (response_rx, storage_handle) = storage_service.subscribe_to_event_stream(receiver);
+        drop(response_rx);
 
-        // Subscribe the storage service to internal event stream
-        storage_service.subscribe_to_event_stream(receiver);
-        storage_service
+        // Periodically compare the derived balances with the token contracts
+        let reconciler_handle = if settings.reconciliation.enabled {
+            let reconciler_handle = reconciler::service::ReconcilerService::new(
+                &settings.eth_node,
+                &settings.reconciliation,
+                storage_service.clone(),
+            )
+            .start(shutdown.clone());
+            Some(reconciler_handle)
+        } else {
+            None
+        };
+        (storage_service, statuses, unconfirmed, subscriptions, enricher_handle, storage_handle, reconciler_handle)
     };
 
-    let app = create_app(Arc::new(log_provider));
+    let app = create_app(log_provider, statuses, unconfirmed);
     let address = format!("{}:{}", settings.server.address, settings.server.port);
     info!("Starting the server on {}", address);
     let listener = tokio::net::TcpListener::bind(address).await.unwrap();
-    axum::serve(listener, app).await.expect("Failed to start Axum server");
+
+    // The server is stopped only after all the received events have been persisted
+    let server_shutdown = CancellationToken::new();
+    let mut server = tokio::spawn(
+        axum::serve(listener, app).with_graceful_shutdown(server_shutdown.clone().cancelled_owned()).into_future(),
+    );
+
+    // A subscription terminates only if it cannot reconnect to any endpoint, in that case the application
+    // exits with an error instead of serving stale data. A replay instead terminates when all the recorded logs
+    // have been sent, then the application stops once they are persisted.
+    let replaying = settings.eth_node.replay_path.is_some();
+    let mut failed = false;
+    tokio::select! {
+        _ = shutdown_signal() => info!("Shutdown signal received"),
+        result = &mut server => {
+            error!("The server terminated unexpectedly: {result:?}");
+            std::process::exit(1);
+        }
+        subscription_failed = async {
+            while let Some(result) = subscriptions.join_next().await {
+                match result {
+                    Ok(Ok(())) if replaying => continue,
+                    Ok(Ok(())) => error!("A subscription terminated unexpectedly"),
+                    Ok(Err(err)) => error!("A subscription terminated with error: {err:?}"),
+                    Err(err) => error!("A subscription task failed: {err:?}"),
+                }
+                return true;
+            }
+            if !replaying {
+                std::future::pending::<()>().await;
+            }
+            info!("All the recorded logs have been replayed");
+            false
+        } => failed = subscription_failed,
+    }
+
+    info!("Stopping the subscriptions...");
+    shutdown.cancel();
+    while let Some(result) = subscriptions.join_next().await {
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => error!("A subscription terminated with error: {err:?}"),
+            Err(err) => error!("A subscription task failed: {err:?}"),
+        }
+    }
+
+    if let Some(reconciler_handle) = reconciler_handle {
+        if let Err(err) = reconciler_handle.await {
+            error!("The reconciliation task failed: {err:?}");
+        }
+    }
+
+    info!("Persisting the received events...");
+    if let Some(enricher_handle) = enricher_handle {
+        match enricher_handle.await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => error!("The enrichment terminated with error: {err:?}"),
+            Err(err) => error!("The enrichment task failed: {err:?}"),
+        }
+    }
+    if let Err(err) = storage_handle.await {
+        error!("The storage task failed: {err:?}");
+    }
+
+    info!("Stopping the server...");
+    server_shutdown.cancel();
+    match server.await {
+        Ok(Ok(())) => info!("Server stopped"),
+        Ok(Err(err)) => error!("The server terminated with error: {err:?}"),
+        Err(err) => error!("The server task failed: {err:?}"),
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Completes when the process receives SIGINT (Ctrl+C) or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async { tokio::signal::ctrl_c().await.expect("Failed to install the Ctrl+C handler") };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
 
 /// Initializes the logger